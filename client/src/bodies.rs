@@ -0,0 +1,123 @@
+use crate::net::ReceivedMessage;
+use crate::{ClientState, LocalPlayer};
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::{KinematicCharacterControllerOutput, PhysicsSet, RigidBody, Velocity};
+use server::net::is_listening;
+use shared::maps::MapNode;
+use shared::protocol::{BodyState, ServerMessage};
+use std::collections::HashMap;
+
+/// Moves the map's dynamic bodies where the server has them, except for a while after our
+/// own pawn pushed one, since the server only sees that push half a round trip later.
+pub struct BodyPlugin;
+
+impl Plugin for BodyPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BodySnapshots>()
+            .add_systems(
+                Update,
+                (receive_snapshots, track_pushes).run_if(not(is_listening)),
+            )
+            .add_systems(OnEnter(ClientState::MainMenu), reset_snapshots)
+            .add_systems(
+                FixedUpdate,
+                apply_snapshots
+                    .before(PhysicsSet::SyncBackend)
+                    .run_if(not(is_listening)),
+            );
+    }
+}
+
+/// Seconds after a push by our pawn during which a body follows our prediction.
+const PUSH_GRACE: f64 = 0.5;
+/// Share of the difference to the server's state made up per snapshot while a body is predicted.
+const PREDICTED_CORRECTION: f32 = 0.1;
+
+/// Latest state of each body the server sent, until the next tick applies it.
+#[derive(Resource, Debug, Default)]
+struct BodySnapshots {
+    last_sequence: Option<u32>,
+    bodies: HashMap<usize, BodyState>,
+}
+
+/// When our pawn last pushed the body, in real seconds.
+#[derive(Component, Debug)]
+struct PushedAt(f64);
+
+fn receive_snapshots(mut evr: EventReader<ReceivedMessage>, mut snapshots: ResMut<BodySnapshots>) {
+    for ReceivedMessage(message) in evr.read() {
+        let (sequence, bodies) = match message {
+            ServerMessage::Bodies { sequence, bodies } => (sequence, bodies),
+            // sequences start over with another server, and bodies with another map
+            ServerMessage::Welcome { .. } | ServerMessage::Map { .. } => {
+                *snapshots = BodySnapshots::default();
+                continue;
+            }
+            _ => continue,
+        };
+        // snapshots may arrive out of order, older ones are of no use,
+        // while the rest of one arrives in further messages of the same sequence
+        if snapshots
+            .last_sequence
+            .is_some_and(|last| (sequence.wrapping_sub(last) as i32) < 0)
+        {
+            continue;
+        }
+        snapshots.last_sequence = Some(*sequence);
+        snapshots
+            .bodies
+            .extend(bodies.iter().map(|body| (body.node as usize, *body)));
+    }
+}
+
+fn reset_snapshots(mut snapshots: ResMut<BodySnapshots>) {
+    *snapshots = BodySnapshots::default();
+}
+
+fn track_pushes(
+    mut commands: Commands,
+    player: Single<&LocalPlayer>,
+    time: Res<Time<Real>>,
+    q_output: Query<&KinematicCharacterControllerOutput>,
+    q_bodies: Query<&RigidBody, With<MapNode>>,
+) {
+    let Ok(output) = q_output.get(player.pawn) else {
+        return;
+    };
+    for collision in &output.collisions {
+        if q_bodies
+            .get(collision.entity)
+            .is_ok_and(|body| *body == RigidBody::Dynamic)
+        {
+            commands
+                .entity(collision.entity)
+                .insert(PushedAt(time.elapsed_secs_f64()));
+        }
+    }
+}
+
+fn apply_snapshots(
+    mut snapshots: ResMut<BodySnapshots>,
+    time: Res<Time<Real>>,
+    mut q_bodies: Query<(&MapNode, &mut Transform, &mut Velocity, Option<&PushedAt>)>,
+) {
+    if snapshots.bodies.is_empty() {
+        return;
+    }
+
+    let now = time.elapsed_secs_f64();
+    for (node, mut transform, mut velocity, pushed_at) in q_bodies.iter_mut() {
+        let Some(state) = snapshots.bodies.remove(&node.index) else {
+            continue;
+        };
+        let predicted = pushed_at.is_some_and(|pushed_at| now - pushed_at.0 < PUSH_GRACE);
+        let correction = if predicted { PREDICTED_CORRECTION } else { 1.0 };
+
+        transform.translation = transform.translation.lerp(state.translation, correction);
+        transform.rotation = transform.rotation.slerp(state.rotation, correction);
+        velocity.linvel = velocity.linvel.lerp(state.linvel, correction);
+        velocity.angvel = velocity.angvel.lerp(state.angvel, correction);
+    }
+    // bodies of another map, or ones not spawned yet
+    snapshots.bodies.clear();
+}
@@ -0,0 +1,364 @@
+use bevy::app::ScheduleRunnerPlugin;
+use bevy::log::LogPlugin;
+use bevy::prelude::*;
+use bevy::time::common_conditions::on_timer;
+use bevy_quinnet::client::certificate::CertificateVerificationMode;
+use bevy_quinnet::client::connection::{
+    ClientEndpointConfiguration, ConnectionEvent, ConnectionFailedEvent, ConnectionLostEvent,
+};
+use bevy_quinnet::client::{QuinnetClient, QuinnetClientPlugin};
+use shared::channels::{self, ClientConnectionExt};
+use shared::consts::{BUILD_HASH, GAME_PORT, PITCH_LIMIT, PROTOCOL_VERSION, TICK_RATE};
+use shared::protocol::{ClientMessage, Hello, ServerMessage};
+use shared::rng::Rng;
+use shared::weapons::WEAPON_SLOTS;
+use shared::{Command, InputEvent, InputKind};
+use std::f32::consts::TAU;
+use std::net::{Ipv6Addr, SocketAddr, SocketAddrV6, ToSocketAddrs};
+use std::time::Duration;
+
+/// How often statistics are written to the log.
+const REPORT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Headless load test, started with `client --bots <count> [--scripted] [--connect <address>]`.
+#[derive(Resource, Debug, Clone)]
+pub struct BotOptions {
+    pub count: usize,
+    pub behavior: Behavior,
+    pub address: String,
+}
+
+impl BotOptions {
+    /// Reads bot options from command line arguments, `None` unless `--bots` is given.
+    pub fn from_args(args: impl Iterator<Item = String>) -> Option<Self> {
+        let mut count = None;
+        let mut behavior = Behavior::Random;
+        let mut address = format!("localhost:{GAME_PORT}");
+
+        let mut args = args.skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--bots" => count = args.next().and_then(|count| count.parse().ok()),
+                "--scripted" => behavior = Behavior::Scripted,
+                "--connect" => address = args.next().unwrap_or(address),
+                _ => {}
+            }
+        }
+
+        Some(Self {
+            count: count?,
+            behavior,
+            address,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Behavior {
+    /// Walks in circles, jumps and fires in a fixed rhythm.
+    Scripted,
+    /// Changes movement, view and buttons at random.
+    Random,
+}
+
+/// Runs bots until the process is stopped, without a window or any rendering.
+pub fn run(options: BotOptions) -> AppExit {
+    bot_app(options).run()
+}
+
+fn bot_app(options: BotOptions) -> App {
+    let mut app = App::new();
+    app.add_plugins(
+        MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(Duration::from_secs_f64(
+            1.0 / TICK_RATE as f64,
+        ))),
+    )
+    .add_plugins(LogPlugin::default())
+    .add_plugins(QuinnetClientPlugin::default())
+    // commands are sent in fixed ticks, which have to match the server's
+    .insert_resource(Time::<Fixed>::from_hz(TICK_RATE as f64))
+    .insert_resource(options)
+    .init_resource::<BotStats>()
+    .add_systems(Startup, connect_bots)
+    .add_systems(PreUpdate, receive_messages)
+    .add_systems(
+        Update,
+        (
+            handle_client_events,
+            report_stats.run_if(on_timer(REPORT_INTERVAL)),
+        ),
+    )
+    .add_systems(FixedUpdate, drive_bots);
+    app
+}
+
+/// A simulated player, owning one connection to the server.
+#[derive(Component)]
+struct Bot {
+    connection: u64,
+    connected: bool,
+    behavior: Behavior,
+    command: Command,
+    rng: Rng,
+    tick: usize,
+}
+
+#[derive(Resource, Default)]
+struct BotStats {
+    /// Last tick time reported by the server, in seconds.
+    server_tick_time: Option<f32>,
+    /// Bytes sent and received over all connections at the last report.
+    last_tx: u64,
+    last_rx: u64,
+}
+
+fn connect_bots(
+    mut commands: Commands,
+    mut client: ResMut<QuinnetClient>,
+    options: Res<BotOptions>,
+    mut evw_exit: EventWriter<AppExit>,
+) {
+    let Some(server_addr) = options
+        .address
+        .to_socket_addrs()
+        .ok()
+        .and_then(|mut addrs| addrs.next())
+    else {
+        error!("Could not resolve {}", options.address);
+        evw_exit.write(AppExit::error());
+        return;
+    };
+
+    info!(
+        "Connecting {} {:?} bots to {server_addr}",
+        options.count, options.behavior
+    );
+
+    for i in 0..options.count {
+        let result = client.open_connection(
+            ClientEndpointConfiguration::from_addrs(
+                server_addr,
+                SocketAddr::V6(SocketAddrV6::new(Ipv6Addr::UNSPECIFIED, 0, 0, 0)),
+            ),
+            CertificateVerificationMode::SkipVerification,
+            channels::configuration(),
+        );
+
+        match result {
+            Ok(connection) => {
+                commands.spawn(Bot {
+                    connection,
+                    connected: false,
+                    behavior: options.behavior,
+                    command: Command::default(),
+                    rng: Rng::new(i as u64),
+                    tick: 0,
+                });
+            }
+            Err(err) => warn!("Could not connect bot {i}: {err:?}"),
+        }
+    }
+}
+
+fn handle_client_events(
+    mut connection_events: EventReader<ConnectionEvent>,
+    mut connection_failed_events: EventReader<ConnectionFailedEvent>,
+    mut connection_lost_events: EventReader<ConnectionLostEvent>,
+    mut client: ResMut<QuinnetClient>,
+    mut commands: Commands,
+    q_bots: Query<(Entity, &Bot)>,
+) {
+    for ev in connection_events.read() {
+        let Some(connection) = client.get_connection_mut_by_id(ev.id) else {
+            continue;
+        };
+        connection.send(ClientMessage::Hello(Hello {
+            version: PROTOCOL_VERSION,
+            build: BUILD_HASH.to_owned(),
+            name: format!("bot{}", ev.id),
+            team: None,
+        }));
+    }
+
+    for ev in connection_failed_events.read() {
+        warn!("Bot connection failed: {:?}", ev.err);
+        if let Some((entity, _)) = q_bots.iter().find(|(_, bot)| bot.connection == ev.id) {
+            commands.entity(entity).despawn();
+        }
+    }
+
+    for ev in connection_lost_events.read() {
+        warn!("Bot connection lost");
+        if let Some((entity, _)) = q_bots.iter().find(|(_, bot)| bot.connection == ev.id) {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+fn drive_bots(mut client: ResMut<QuinnetClient>, mut q_bots: Query<&mut Bot>) {
+    for mut bot in q_bots.iter_mut() {
+        if !bot.connected {
+            continue;
+        }
+
+        bot.think();
+        let Some(connection) = client.get_connection_mut_by_id(bot.connection) else {
+            continue;
+        };
+        connection.send(ClientMessage::Command(bot.command.clone()));
+    }
+}
+
+impl Bot {
+    /// Produces the command for the next tick.
+    fn think(&mut self) {
+        let dt = 1.0 / TICK_RATE as f32;
+        let tick = self.tick;
+        self.tick += 1;
+
+        let command = &mut self.command;
+        command.jump = false;
+        command.fire = false;
+        command.reload = false;
+        command.weapon = None;
+        command.events.clear();
+
+        match self.behavior {
+            Behavior::Scripted => {
+                command.forward = true;
+                command.angle.x = (command.angle.x + dt).rem_euclid(TAU);
+                command.jump = tick.is_multiple_of(2 * TICK_RATE);
+                command.fire = tick % TICK_RATE < 10;
+            }
+            Behavior::Random => {
+                let rng = &mut self.rng;
+                // hold movement keys for a while, like a player would
+                if tick.is_multiple_of(TICK_RATE / 2) {
+                    command.forward = rng.chance(0.6);
+                    command.backward = !command.forward && rng.chance(0.3);
+                    command.left = rng.chance(0.3);
+                    command.right = !command.left && rng.chance(0.3);
+                    command.crouch = rng.chance(0.1);
+                }
+                command.angle.x = (command.angle.x + (rng.fraction() - 0.5) * 0.2).rem_euclid(TAU);
+                command.angle.y = (command.angle.y + (rng.fraction() - 0.5) * 0.1)
+                    .clamp(-PITCH_LIMIT, PITCH_LIMIT);
+                command.jump = rng.chance(0.02);
+                command.fire = rng.chance(0.1);
+                command.reload = rng.chance(0.005);
+                if rng.chance(0.005) {
+                    command.weapon = Some((rng.next_u64() % WEAPON_SLOTS as u64) as u8);
+                }
+            }
+        }
+
+        for (pressed, kind) in [
+            (command.jump, InputKind::Jump),
+            (command.fire, InputKind::Fire),
+        ] {
+            if pressed {
+                command.events.push(InputEvent {
+                    kind,
+                    pressed: true,
+                    time: 0.0,
+                });
+            }
+        }
+    }
+}
+
+fn receive_messages(
+    mut client: ResMut<QuinnetClient>,
+    mut stats: ResMut<BotStats>,
+    mut commands: Commands,
+    mut q_bots: Query<(Entity, &mut Bot)>,
+) {
+    for (entity, mut bot) in q_bots.iter_mut() {
+        let Some(connection) = client.get_connection_mut_by_id(bot.connection) else {
+            continue;
+        };
+        let mut rejected = false;
+        while let Some(message) = connection.receive::<ServerMessage>() {
+            match message {
+                // bots only start playing once they joined
+                ServerMessage::Welcome { .. } => bot.connected = true,
+                ServerMessage::Rejected { reason } => {
+                    warn!("Bot rejected: {reason}");
+                    rejected = true;
+                }
+                ServerMessage::Stats { tick_time } => stats.server_tick_time = Some(tick_time),
+                ServerMessage::Heartbeat
+                | ServerMessage::Expired
+                | ServerMessage::Health { .. }
+                | ServerMessage::Respawn { .. }
+                | ServerMessage::Loadout { .. }
+                | ServerMessage::Launched { .. }
+                | ServerMessage::Detonated { .. }
+                | ServerMessage::Map { .. }
+                | ServerMessage::Bodies { .. }
+                | ServerMessage::Movers(_)
+                | ServerMessage::Scoreboard(_)
+                | ServerMessage::Flags(_) => {}
+            }
+        }
+
+        if rejected {
+            let _ = client.close_connection(bot.connection);
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+fn report_stats(client: Res<QuinnetClient>, mut stats: ResMut<BotStats>, q_bots: Query<&Bot>) {
+    let connection_stats = q_bots
+        .iter()
+        .filter(|bot| bot.connected)
+        .filter_map(|bot| client.get_connection_by_id(bot.connection))
+        .filter_map(|connection| connection.connection_stats())
+        .collect::<Vec<_>>();
+
+    let tx = connection_stats.iter().map(|s| s.udp_tx.bytes).sum::<u64>();
+    let rx = connection_stats.iter().map(|s| s.udp_rx.bytes).sum::<u64>();
+    let secs = REPORT_INTERVAL.as_secs_f64();
+    let up = tx.saturating_sub(stats.last_tx) as f64 / secs / 1024.0;
+    let down = rx.saturating_sub(stats.last_rx) as f64 / secs / 1024.0;
+    stats.last_tx = tx;
+    stats.last_rx = rx;
+
+    let rtts = connection_stats
+        .iter()
+        .map(|s| s.path.rtt.as_secs_f64() * 1000.0)
+        .collect::<Vec<_>>();
+    let rtt_min = rtts.iter().copied().reduce(f64::min).unwrap_or_default();
+    let rtt_max = rtts.iter().copied().reduce(f64::max).unwrap_or_default();
+    let rtt_avg = rtts.iter().sum::<f64>() / rtts.len().max(1) as f64;
+
+    let tick_time = stats
+        .server_tick_time
+        .map(|t| format!("{:.2} ms", t * 1000.0))
+        .unwrap_or_else(|| "unknown".to_owned());
+
+    info!(
+        "{}/{} bots connected, server tick {tick_time}, up {up:.1} KiB/s, down {down:.1} KiB/s, rtt min {rtt_min:.1} avg {rtt_avg:.1} max {rtt_max:.1} ms",
+        connection_stats.len(),
+        q_bots.iter().count(),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bots_tick_at_the_tick_rate() {
+        let app = bot_app(BotOptions {
+            count: 1,
+            behavior: Behavior::Scripted,
+            address: format!("localhost:{GAME_PORT}"),
+        });
+        assert_eq!(
+            app.world().resource::<Time<Fixed>>().timestep(),
+            Duration::from_secs_f64(1.0 / TICK_RATE as f64)
+        );
+    }
+}
@@ -0,0 +1,235 @@
+use crate::net::ReceivedMessage;
+use crate::{ClientState, LocalPlayer};
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use server::net::is_listening;
+use shared::health::{Dead, Health};
+use shared::interpolate::{Interpolate, InterpolateTranslation};
+use shared::pawns::PossessExt;
+use shared::pawns::fps::EYE_OFFSET;
+use shared::protocol::ServerMessage;
+use shared::session::Actor;
+use shared::weapons::{Loadout, LoadoutState};
+use std::collections::VecDeque;
+
+/// Follows the server's word on health, death and weapons, and shows the player's health and ammo.
+pub struct CombatPlugin;
+
+impl Plugin for CombatPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PredictedLoadouts>()
+            .add_event::<RespawnEvent>()
+            .add_systems(Startup, spawn_hud)
+            .add_systems(FixedPreUpdate, respawn)
+            .add_systems(FixedPostUpdate, record_loadout.run_if(not(is_listening)))
+            .add_systems(
+                Update,
+                (
+                    apply_health,
+                    apply_loadouts,
+                    spectate_while_dead,
+                    update_hud,
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// Seconds a predicted state of the player's loadout is remembered, longer than a round trip.
+const PREDICTION_WINDOW: f32 = 1.0;
+
+/// States the player's loadout was predicted to be in lately, with when each was last seen.
+/// The server's states lag behind ours, one that matches any of these agrees with the prediction.
+#[derive(Resource, Default)]
+struct PredictedLoadouts(VecDeque<(f32, LoadoutState)>);
+
+#[derive(Component)]
+struct HudText;
+
+/// A pawn the server brought back to life, moved between ticks so interpolation does not undo it.
+#[derive(Event)]
+struct RespawnEvent {
+    entity: Entity,
+    translation: Vec3,
+}
+
+fn apply_health(
+    mut commands: Commands,
+    mut evr: EventReader<ReceivedMessage>,
+    mut evw_respawn: EventWriter<RespawnEvent>,
+    mut q_actors: Query<(Entity, &Actor, &mut Health)>,
+) {
+    for ReceivedMessage(message) in evr.read() {
+        match *message {
+            ServerMessage::Health { actor, health } => {
+                let Some((entity, _, mut current)) =
+                    q_actors.iter_mut().find(|(_, a, _)| a.id() == actor)
+                else {
+                    continue;
+                };
+                current.current = health;
+                // only the respawn brings it back, health alone does not
+                if current.is_dead() {
+                    commands.entity(entity).insert(Dead);
+                }
+            }
+            ServerMessage::Respawn { actor, translation } => {
+                if let Some((entity, ..)) = q_actors.iter().find(|(_, a, _)| a.id() == actor) {
+                    evw_respawn.write(RespawnEvent {
+                        entity,
+                        translation,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn respawn(
+    mut commands: Commands,
+    mut evr: EventReader<RespawnEvent>,
+    mut q_pawns: Query<(
+        &mut Transform,
+        Option<&mut InterpolateTranslation>,
+        Option<&mut Velocity>,
+        Option<&mut Loadout>,
+    )>,
+) {
+    for event in evr.read() {
+        let Ok((mut transform, interpolate, velocity, loadout)) = q_pawns.get_mut(event.entity)
+        else {
+            continue;
+        };
+        match interpolate {
+            Some(mut interpolate) => interpolate.teleport(&mut transform, event.translation),
+            None => transform.translation = event.translation,
+        }
+        if let Some(mut velocity) = velocity {
+            *velocity = Velocity::zero();
+        }
+        if let Some(mut loadout) = loadout {
+            loadout.refill();
+        }
+        commands.entity(event.entity).remove::<Dead>();
+    }
+}
+
+fn record_loadout(
+    time: Res<Time>,
+    player: Single<&LocalPlayer>,
+    q_loadouts: Query<&Loadout>,
+    mut predicted: ResMut<PredictedLoadouts>,
+) {
+    let Ok(loadout) = q_loadouts.get(player.pawn) else {
+        return;
+    };
+    let now = time.elapsed_secs();
+    let state = loadout.state();
+    let history = &mut predicted.0;
+    match history.back_mut() {
+        Some((seen, last)) if *last == state => *seen = now,
+        _ => history.push_back((now, state)),
+    }
+    while history
+        .front()
+        .is_some_and(|(seen, _)| *seen < now - PREDICTION_WINDOW)
+    {
+        history.pop_front();
+    }
+}
+
+/// Snaps loadouts to the server's state, the player's only when it went a way we did not predict.
+fn apply_loadouts(
+    mut evr: EventReader<ReceivedMessage>,
+    player: Single<&LocalPlayer>,
+    mut predicted: ResMut<PredictedLoadouts>,
+    mut q_actors: Query<(Entity, &Actor, &mut Loadout)>,
+) {
+    for ReceivedMessage(message) in evr.read() {
+        let ServerMessage::Loadout { actor, state } = message else {
+            continue;
+        };
+        let Some((entity, _, mut loadout)) = q_actors.iter_mut().find(|(_, a, _)| a.id() == *actor)
+        else {
+            continue;
+        };
+        if entity == player.pawn {
+            if predicted.0.iter().any(|(_, predicted)| predicted == state) {
+                continue;
+            }
+            predicted.0.clear();
+        }
+        loadout.apply(state);
+    }
+}
+
+/// Dead players watch from the spectator, starting where they fell, until they respawn.
+fn spectate_while_dead(
+    mut commands: Commands,
+    player: Single<(Entity, &LocalPlayer)>,
+    q_died: Query<&Transform, (With<Dead>, Added<Dead>)>,
+    mut q_spectator: Query<&mut Transform, Without<Dead>>,
+    mut removed_dead: RemovedComponents<Dead>,
+) {
+    let (entity, player) = *player;
+    if let Ok(transform) = q_died.get(player.pawn) {
+        if let Ok(mut spectator) = q_spectator.get_mut(player.spectator) {
+            spectator.translation = transform.translation + EYE_OFFSET;
+        }
+        commands.entity(entity).possess(player.spectator);
+    }
+    if removed_dead.read().any(|removed| removed == player.pawn) {
+        commands.entity(entity).possess(player.pawn);
+    }
+}
+
+fn spawn_hud(mut commands: Commands) {
+    commands.spawn((
+        HudText,
+        Text::default(),
+        TextFont {
+            font_size: 32.0,
+            ..Default::default()
+        },
+        Node {
+            position_type: PositionType::Absolute,
+            left: Val::Px(16.0),
+            bottom: Val::Px(16.0),
+            ..Default::default()
+        },
+        Visibility::Hidden,
+    ));
+}
+
+fn update_hud(
+    player: Single<&LocalPlayer>,
+    state: Res<State<ClientState>>,
+    q_pawn: Query<(&Health, &Loadout)>,
+    text: Single<(&mut Text, &mut Visibility), With<HudText>>,
+) {
+    let (mut text, mut visibility) = text.into_inner();
+    let in_game = matches!(state.get(), ClientState::InGame | ClientState::Paused);
+    visibility.set_if_neq(if in_game {
+        Visibility::Inherited
+    } else {
+        Visibility::Hidden
+    });
+
+    if let Ok((health, loadout)) = q_pawn.get(player.pawn) {
+        let mut shown = health.current.ceil().to_string();
+        if let Some(weapon) = loadout.current() {
+            shown += &format!(
+                "    {} {}/{}",
+                weapon.stats.name, weapon.ammo, weapon.stats.magazine
+            );
+            if weapon.reloading.is_some() {
+                shown += " reloading";
+            }
+        }
+        if text.0 != shown {
+            text.0 = shown;
+        }
+    }
+}
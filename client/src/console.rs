@@ -0,0 +1,283 @@
+use crate::cursor::{CursorState, UiLayer};
+use bevy::input::InputSystem;
+use bevy::input::keyboard::{Key, KeyboardInput, keyboard_input_system};
+use bevy::input::mouse::mouse_button_input_system;
+use bevy::log::BoxedLayer;
+use bevy::log::tracing::field::{Field, Visit};
+use bevy::log::tracing::{Level, Subscriber};
+use bevy::log::tracing_subscriber::Layer;
+use bevy::log::tracing_subscriber::layer::Context;
+use bevy::prelude::*;
+use shared::console::CommandEvent;
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::mem;
+use std::sync::Mutex;
+use std::sync::mpsc::{self, Receiver, Sender};
+
+/// Drop-down console to run commands and read the log in game, opened with the key below escape.
+pub struct ConsolePlugin;
+
+impl Plugin for ConsolePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Console>()
+            .add_systems(
+                PreUpdate,
+                // before anything else reads the keyboard and mouse, which the open console takes
+                handle_console_input
+                    .in_set(InputSystem)
+                    .after(keyboard_input_system)
+                    .after(mouse_button_input_system),
+            )
+            .add_systems(Update, (read_log, show_console).chain());
+    }
+}
+
+const TOGGLE_KEY: KeyCode = KeyCode::Backquote;
+
+/// Lines kept in the console, older ones are dropped.
+const MAX_LINES: usize = 200;
+
+/// Lines of the log and commands entered, along with the one being typed.
+#[derive(Resource, Default)]
+struct Console {
+    open: bool,
+    input: String,
+    lines: VecDeque<String>,
+}
+
+impl Console {
+    fn push(&mut self, line: String) {
+        if self.lines.len() >= MAX_LINES {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(line);
+    }
+
+    fn text(&self) -> String {
+        let mut text = self.lines.iter().fold(String::new(), |mut text, line| {
+            text.push_str(line);
+            text.push('\n');
+            text
+        });
+        text.push_str("> ");
+        text.push_str(&self.input);
+        text
+    }
+}
+
+#[derive(Component)]
+struct ConsoleRoot;
+
+#[derive(Component)]
+struct ConsoleText;
+
+fn handle_console_input(
+    mut console: ResMut<Console>,
+    mut cursor: ResMut<CursorState>,
+    mut keyboard: ResMut<ButtonInput<KeyCode>>,
+    mut mouse: ResMut<ButtonInput<MouseButton>>,
+    mut keyboard_events: ResMut<Events<KeyboardInput>>,
+    mut evw: EventWriter<CommandEvent>,
+) {
+    let toggle = keyboard.just_pressed(TOGGLE_KEY);
+    if !console.open && !toggle {
+        return;
+    }
+
+    // neither the game nor the menus see what goes into the console
+    let events = keyboard_events.drain().collect::<Vec<_>>();
+    let close = console.open && (toggle || keyboard.just_pressed(KeyCode::Escape));
+    keyboard.reset_all();
+    mouse.reset_all();
+
+    if close {
+        console.open = false;
+        cursor.recapture();
+        return;
+    }
+    if !console.open {
+        // the toggle key is not typed into the fresh console
+        console.open = true;
+        return;
+    }
+
+    for event in events.iter().filter(|event| event.state.is_pressed()) {
+        match &event.logical_key {
+            Key::Enter => {
+                let line = mem::take(&mut console.input);
+                if let Some(command) = CommandEvent::parse(&line) {
+                    evw.write(command);
+                }
+                console.push(format!("> {line}"));
+            }
+            Key::Backspace => {
+                console.input.pop();
+            }
+            Key::Character(text) => console
+                .input
+                .extend(text.chars().filter(|c| !c.is_control())),
+            _ => {}
+        }
+    }
+}
+
+fn read_log(lines: Option<Res<LogLines>>, mut console: ResMut<Console>) {
+    let Some(Ok(rx)) = lines.as_ref().map(|lines| lines.0.lock()) else {
+        return;
+    };
+    for line in rx.try_iter() {
+        console.push(line);
+    }
+}
+
+fn show_console(
+    mut commands: Commands,
+    console: Res<Console>,
+    q_root: Query<Entity, With<ConsoleRoot>>,
+    mut q_text: Query<&mut Text, With<ConsoleText>>,
+) {
+    if !console.is_changed() {
+        return;
+    }
+
+    match (console.open, q_root.single()) {
+        (true, Err(_)) => {
+            commands.spawn((
+                ConsoleRoot,
+                UiLayer,
+                Node {
+                    position_type: PositionType::Absolute,
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(50.0),
+                    flex_direction: FlexDirection::Column,
+                    // the latest lines at the bottom stay in view, older ones are cut off at the top
+                    justify_content: JustifyContent::FlexEnd,
+                    padding: UiRect::all(Val::Px(8.0)),
+                    overflow: Overflow::clip(),
+                    ..Default::default()
+                },
+                BackgroundColor(Color::BLACK.with_alpha(0.8)),
+                // above the menus
+                GlobalZIndex(1),
+                children![(
+                    ConsoleText,
+                    Text::new(console.text()),
+                    TextFont {
+                        font_size: 14.0,
+                        ..Default::default()
+                    },
+                )],
+            ));
+        }
+        (false, Ok(root)) => {
+            commands.entity(root).despawn();
+        }
+        _ => {
+            for mut text in q_text.iter_mut() {
+                text.0 = console.text();
+            }
+        }
+    }
+}
+
+/// Log messages on their way into the console.
+#[derive(Resource)]
+struct LogLines(Mutex<Receiver<String>>);
+
+/// Passes log messages on to the console, to be set as the custom layer of the [bevy::log::LogPlugin].
+pub fn log_layer(app: &mut App) -> Option<BoxedLayer> {
+    let (tx, rx) = mpsc::channel();
+    app.insert_resource(LogLines(Mutex::new(rx)));
+    Some(Box::new(LogLayer(tx)))
+}
+
+struct LogLayer(Sender<String>);
+
+impl<S: Subscriber> Layer<S> for LogLayer {
+    fn on_event(&self, event: &bevy::log::tracing::Event<'_>, _ctx: Context<'_, S>) {
+        let mut message = None;
+        event.record(&mut MessageVisitor(&mut message));
+        let Some(message) = message else {
+            return;
+        };
+        let level = event.metadata().level();
+        let line = if [Level::WARN, Level::ERROR].contains(level) {
+            format!("{level}: {message}")
+        } else {
+            message
+        };
+        // nobody listens anymore once the app shut down
+        let _ = self.0.send(line);
+    }
+}
+
+/// Picks the message out of the fields of a log event.
+struct MessageVisitor<'a>(&'a mut Option<String>);
+
+impl Visit for MessageVisitor<'_> {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name() == "message" {
+            *self.0 = Some(format!("{value:?}"));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::input::ButtonState;
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_event::<KeyboardInput>()
+            .add_event::<CommandEvent>()
+            .init_resource::<ButtonInput<KeyCode>>()
+            .init_resource::<ButtonInput<MouseButton>>()
+            .init_resource::<CursorState>()
+            .init_resource::<Console>()
+            .add_systems(Update, handle_console_input);
+        app
+    }
+
+    fn type_key(app: &mut App, key_code: KeyCode, logical_key: Key) {
+        let world = app.world_mut();
+        world.resource_mut::<ButtonInput<KeyCode>>().press(key_code);
+        world.send_event(KeyboardInput {
+            key_code,
+            logical_key,
+            state: ButtonState::Pressed,
+            text: None,
+            repeat: false,
+            window: Entity::PLACEHOLDER,
+        });
+        app.update();
+    }
+
+    #[test]
+    fn entered_lines_run_as_commands() {
+        let mut app = app();
+        type_key(&mut app, TOGGLE_KEY, Key::Character("`".into()));
+        assert!(app.world().resource::<Console>().open);
+
+        for c in "fov 90".chars() {
+            type_key(
+                &mut app,
+                KeyCode::KeyA,
+                Key::Character(c.to_string().into()),
+            );
+        }
+        type_key(&mut app, KeyCode::Enter, Key::Enter);
+
+        let events = app.world().resource::<Events<CommandEvent>>();
+        let commands = events.iter_current_update_events().collect::<Vec<_>>();
+        assert_eq!(commands.len(), 1);
+        assert_eq!(**commands[0], ["fov", "90"]);
+        // the game never saw the keys
+        assert!(
+            !app.world()
+                .resource::<ButtonInput<KeyCode>>()
+                .pressed(KeyCode::KeyA)
+        );
+    }
+}
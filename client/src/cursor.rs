@@ -0,0 +1,66 @@
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+use bevy::window::{CursorGrabMode, PrimaryWindow};
+
+/// Grabs the cursor while playing, and releases it for everything else.
+pub struct CursorPlugin;
+
+impl Plugin for CursorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CursorState>()
+            .add_systems(PreUpdate, update_cursor.after(InputSystem));
+    }
+}
+
+/// Marks the root of an interface which needs the cursor, e.g. a menu or the console.
+/// The cursor stays released as long as any of these exist.
+#[derive(Component, Default)]
+pub struct UiLayer;
+
+#[derive(Resource, Default)]
+pub struct CursorState {
+    grabbed: bool,
+    /// Set when the player let go of the cursor, until they click back into the window.
+    released: bool,
+}
+
+impl CursorState {
+    /// Whether the cursor is captured, and mouse motion should turn the view.
+    pub fn grabbed(&self) -> bool {
+        self.grabbed
+    }
+
+    /// Takes the cursor back, e.g. when closing a menu, without waiting for a click.
+    pub fn recapture(&mut self) {
+        self.released = false;
+    }
+}
+
+fn update_cursor(
+    mut state: ResMut<CursorState>,
+    mut window: Single<&mut Window, With<PrimaryWindow>>,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    q_layers: Query<(), With<UiLayer>>,
+) {
+    let blocked = !q_layers.is_empty();
+
+    if !window.focused || keyboard.just_pressed(KeyCode::Escape) {
+        state.released = true;
+    } else if state.released && !blocked && mouse.just_pressed(MouseButton::Left) {
+        state.released = false;
+    }
+
+    let grabbed = window.focused && !blocked && !state.released;
+    if state.grabbed == grabbed && window.cursor_options.visible != grabbed {
+        return;
+    }
+
+    state.grabbed = grabbed;
+    window.cursor_options.visible = !grabbed;
+    window.cursor_options.grab_mode = if grabbed {
+        CursorGrabMode::Locked
+    } else {
+        CursorGrabMode::None
+    };
+}
@@ -0,0 +1,108 @@
+use crate::net::ReceivedMessage;
+use bevy::prelude::*;
+use shared::game::{FlagPlacement, FlagPlacements};
+use shared::protocol::ServerMessage;
+use shared::session::{Actor, Team};
+
+/// Shows the flags of capture the flag wherever the server placed them.
+pub struct FlagPlugin;
+
+impl Plugin for FlagPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FlagPlacements>()
+            .add_systems(Update, (receive_flags, spawn_flags, place_flags).chain());
+    }
+}
+
+/// Height of a flag's pole.
+const POLE_HEIGHT: f32 = 2.0;
+
+/// Shown for the flag of a team.
+#[derive(Component)]
+struct FlagMarker(Team);
+
+fn receive_flags(mut evr: EventReader<ReceivedMessage>, mut placements: ResMut<FlagPlacements>) {
+    for ReceivedMessage(message) in evr.read() {
+        match message {
+            ServerMessage::Flags(next) => {
+                placements.set_if_neq(next.clone());
+            }
+            // servers without flags never say so
+            ServerMessage::Welcome { .. } => {
+                placements.set_if_neq(FlagPlacements::default());
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Spawns a flag for every team which has one, and despawns the rest.
+fn spawn_flags(
+    mut commands: Commands,
+    placements: Res<FlagPlacements>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    q_flags: Query<(Entity, &FlagMarker)>,
+) {
+    if !placements.is_changed() {
+        return;
+    }
+    let has_flag = |team: Team| placements.0.iter().any(|(t, _)| *t == team);
+
+    for (entity, marker) in q_flags.iter() {
+        if !has_flag(marker.0) {
+            commands.entity(entity).despawn();
+        }
+    }
+    for team in Team::ALL {
+        if !has_flag(team) || q_flags.iter().any(|(_, marker)| marker.0 == team) {
+            continue;
+        }
+        let color = match team {
+            Team::Red => Color::srgb(0.9, 0.1, 0.1),
+            Team::Blue => Color::srgb(0.1, 0.2, 0.9),
+        };
+        // the flag's origin is the foot of the pole
+        commands
+            .spawn((
+                FlagMarker(team),
+                Transform::default(),
+                Visibility::default(),
+            ))
+            .with_children(|parent| {
+                parent.spawn((
+                    Mesh3d(meshes.add(Cylinder::new(0.03, POLE_HEIGHT))),
+                    MeshMaterial3d(materials.add(Color::srgb(0.6, 0.6, 0.6))),
+                    Transform::from_xyz(0.0, POLE_HEIGHT / 2.0, 0.0),
+                ));
+                parent.spawn((
+                    Mesh3d(meshes.add(Cuboid::new(0.6, 0.4, 0.02))),
+                    MeshMaterial3d(materials.add(color)),
+                    Transform::from_xyz(0.3, POLE_HEIGHT - 0.2, 0.0),
+                ));
+            });
+    }
+}
+
+/// Moves flags to where they lie, or along with the pawns carrying them.
+fn place_flags(
+    placements: Res<FlagPlacements>,
+    mut q_flags: Query<(&FlagMarker, &mut Transform)>,
+    q_actors: Query<(&Actor, &Transform), Without<FlagMarker>>,
+) {
+    for (marker, mut transform) in q_flags.iter_mut() {
+        let Some((_, placement)) = placements.0.iter().find(|(team, _)| *team == marker.0) else {
+            continue;
+        };
+        let translation = match *placement {
+            FlagPlacement::At(translation) => Some(translation),
+            FlagPlacement::Carried(actor) => q_actors
+                .iter()
+                .find(|(a, _)| a.id() == actor)
+                .map(|(_, carrier)| carrier.translation),
+        };
+        if let Some(translation) = translation {
+            transform.translation = translation;
+        }
+    }
+}
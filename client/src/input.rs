@@ -0,0 +1,458 @@
+use bevy::input::InputSystem;
+use bevy::input::gamepad::{GamepadAxis, GamepadButton};
+use bevy::prelude::*;
+use shared::console::{Args, CommandAppExt, CommandEvent};
+use std::fs;
+
+/// Maps keyboard, mouse and gamepad inputs to [Action]s, which can be rebound through commands.
+pub struct InputPlugin;
+
+/// Bindings are stored as commands, and executed again on startup.
+const BINDINGS_PATH: &str = "bindings.cfg";
+
+impl Plugin for InputPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<InputMap>()
+            .init_resource::<ActionState>()
+            .add_systems(PreUpdate, update_action_state.after(InputSystem))
+            .add_systems(Startup, load_bindings)
+            .add_systems(Last, save_bindings)
+            .add_command("bind", bind)
+            .add_command("unbind", unbind)
+            .add_command("unbindall", unbind_all);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    Forward,
+    Backward,
+    Left,
+    Right,
+    Up,
+    Down,
+    Jump,
+    Sneak,
+    Crouch,
+    Fire,
+    Reload,
+    Use,
+    Weapon1,
+    Weapon2,
+    Weapon3,
+    Weapon4,
+    Scoreboard,
+    LookLeft,
+    LookRight,
+    LookUp,
+    LookDown,
+}
+
+impl Action {
+    const NAMES: &[(&str, Action)] = &[
+        ("forward", Action::Forward),
+        ("back", Action::Backward),
+        ("left", Action::Left),
+        ("right", Action::Right),
+        ("up", Action::Up),
+        ("down", Action::Down),
+        ("jump", Action::Jump),
+        ("sneak", Action::Sneak),
+        ("crouch", Action::Crouch),
+        ("fire", Action::Fire),
+        ("reload", Action::Reload),
+        ("use", Action::Use),
+        ("weapon1", Action::Weapon1),
+        ("weapon2", Action::Weapon2),
+        ("weapon3", Action::Weapon3),
+        ("weapon4", Action::Weapon4),
+        ("scoreboard", Action::Scoreboard),
+        ("lookleft", Action::LookLeft),
+        ("lookright", Action::LookRight),
+        ("lookup", Action::LookUp),
+        ("lookdown", Action::LookDown),
+    ];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::NAMES
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|&(_, action)| action)
+    }
+
+    pub fn name(&self) -> &'static str {
+        Self::NAMES.iter().find(|(_, a)| a == self).unwrap().0
+    }
+}
+
+/// A physical input that can be bound to an [Action].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    GamepadButton(GamepadButton),
+    /// One direction of a gamepad axis, `positive` selects which one.
+    GamepadAxis {
+        axis: GamepadAxis,
+        positive: bool,
+    },
+}
+
+impl Binding {
+    const NAMES: &[(&str, Binding)] = &[
+        ("a", Binding::Key(KeyCode::KeyA)),
+        ("b", Binding::Key(KeyCode::KeyB)),
+        ("c", Binding::Key(KeyCode::KeyC)),
+        ("d", Binding::Key(KeyCode::KeyD)),
+        ("e", Binding::Key(KeyCode::KeyE)),
+        ("f", Binding::Key(KeyCode::KeyF)),
+        ("g", Binding::Key(KeyCode::KeyG)),
+        ("h", Binding::Key(KeyCode::KeyH)),
+        ("i", Binding::Key(KeyCode::KeyI)),
+        ("j", Binding::Key(KeyCode::KeyJ)),
+        ("k", Binding::Key(KeyCode::KeyK)),
+        ("l", Binding::Key(KeyCode::KeyL)),
+        ("m", Binding::Key(KeyCode::KeyM)),
+        ("n", Binding::Key(KeyCode::KeyN)),
+        ("o", Binding::Key(KeyCode::KeyO)),
+        ("p", Binding::Key(KeyCode::KeyP)),
+        ("q", Binding::Key(KeyCode::KeyQ)),
+        ("r", Binding::Key(KeyCode::KeyR)),
+        ("s", Binding::Key(KeyCode::KeyS)),
+        ("t", Binding::Key(KeyCode::KeyT)),
+        ("u", Binding::Key(KeyCode::KeyU)),
+        ("v", Binding::Key(KeyCode::KeyV)),
+        ("w", Binding::Key(KeyCode::KeyW)),
+        ("x", Binding::Key(KeyCode::KeyX)),
+        ("y", Binding::Key(KeyCode::KeyY)),
+        ("z", Binding::Key(KeyCode::KeyZ)),
+        ("0", Binding::Key(KeyCode::Digit0)),
+        ("1", Binding::Key(KeyCode::Digit1)),
+        ("2", Binding::Key(KeyCode::Digit2)),
+        ("3", Binding::Key(KeyCode::Digit3)),
+        ("4", Binding::Key(KeyCode::Digit4)),
+        ("5", Binding::Key(KeyCode::Digit5)),
+        ("6", Binding::Key(KeyCode::Digit6)),
+        ("7", Binding::Key(KeyCode::Digit7)),
+        ("8", Binding::Key(KeyCode::Digit8)),
+        ("9", Binding::Key(KeyCode::Digit9)),
+        ("f1", Binding::Key(KeyCode::F1)),
+        ("f2", Binding::Key(KeyCode::F2)),
+        ("f3", Binding::Key(KeyCode::F3)),
+        ("f4", Binding::Key(KeyCode::F4)),
+        ("f5", Binding::Key(KeyCode::F5)),
+        ("f6", Binding::Key(KeyCode::F6)),
+        ("f7", Binding::Key(KeyCode::F7)),
+        ("f8", Binding::Key(KeyCode::F8)),
+        ("f9", Binding::Key(KeyCode::F9)),
+        ("f10", Binding::Key(KeyCode::F10)),
+        ("f11", Binding::Key(KeyCode::F11)),
+        ("f12", Binding::Key(KeyCode::F12)),
+        ("space", Binding::Key(KeyCode::Space)),
+        ("tab", Binding::Key(KeyCode::Tab)),
+        ("enter", Binding::Key(KeyCode::Enter)),
+        ("escape", Binding::Key(KeyCode::Escape)),
+        ("backspace", Binding::Key(KeyCode::Backspace)),
+        ("capslock", Binding::Key(KeyCode::CapsLock)),
+        ("lshift", Binding::Key(KeyCode::ShiftLeft)),
+        ("rshift", Binding::Key(KeyCode::ShiftRight)),
+        ("lctrl", Binding::Key(KeyCode::ControlLeft)),
+        ("rctrl", Binding::Key(KeyCode::ControlRight)),
+        ("lalt", Binding::Key(KeyCode::AltLeft)),
+        ("ralt", Binding::Key(KeyCode::AltRight)),
+        ("uparrow", Binding::Key(KeyCode::ArrowUp)),
+        ("downarrow", Binding::Key(KeyCode::ArrowDown)),
+        ("leftarrow", Binding::Key(KeyCode::ArrowLeft)),
+        ("rightarrow", Binding::Key(KeyCode::ArrowRight)),
+        ("mouse1", Binding::Mouse(MouseButton::Left)),
+        ("mouse2", Binding::Mouse(MouseButton::Right)),
+        ("mouse3", Binding::Mouse(MouseButton::Middle)),
+        ("mouse4", Binding::Mouse(MouseButton::Back)),
+        ("mouse5", Binding::Mouse(MouseButton::Forward)),
+        ("pad_a", Binding::GamepadButton(GamepadButton::South)),
+        ("pad_b", Binding::GamepadButton(GamepadButton::East)),
+        ("pad_x", Binding::GamepadButton(GamepadButton::West)),
+        ("pad_y", Binding::GamepadButton(GamepadButton::North)),
+        ("pad_lb", Binding::GamepadButton(GamepadButton::LeftTrigger)),
+        (
+            "pad_rb",
+            Binding::GamepadButton(GamepadButton::RightTrigger),
+        ),
+        (
+            "pad_lt",
+            Binding::GamepadButton(GamepadButton::LeftTrigger2),
+        ),
+        (
+            "pad_rt",
+            Binding::GamepadButton(GamepadButton::RightTrigger2),
+        ),
+        ("pad_ls", Binding::GamepadButton(GamepadButton::LeftThumb)),
+        ("pad_rs", Binding::GamepadButton(GamepadButton::RightThumb)),
+        ("pad_start", Binding::GamepadButton(GamepadButton::Start)),
+        ("pad_select", Binding::GamepadButton(GamepadButton::Select)),
+        ("pad_up", Binding::GamepadButton(GamepadButton::DPadUp)),
+        ("pad_down", Binding::GamepadButton(GamepadButton::DPadDown)),
+        ("pad_left", Binding::GamepadButton(GamepadButton::DPadLeft)),
+        (
+            "pad_right",
+            Binding::GamepadButton(GamepadButton::DPadRight),
+        ),
+        ("lstick_left", Binding::axis(GamepadAxis::LeftStickX, false)),
+        ("lstick_right", Binding::axis(GamepadAxis::LeftStickX, true)),
+        ("lstick_down", Binding::axis(GamepadAxis::LeftStickY, false)),
+        ("lstick_up", Binding::axis(GamepadAxis::LeftStickY, true)),
+        (
+            "rstick_left",
+            Binding::axis(GamepadAxis::RightStickX, false),
+        ),
+        (
+            "rstick_right",
+            Binding::axis(GamepadAxis::RightStickX, true),
+        ),
+        (
+            "rstick_down",
+            Binding::axis(GamepadAxis::RightStickY, false),
+        ),
+        ("rstick_up", Binding::axis(GamepadAxis::RightStickY, true)),
+    ];
+
+    const fn axis(axis: GamepadAxis, positive: bool) -> Self {
+        Binding::GamepadAxis { axis, positive }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::NAMES
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|&(_, binding)| binding)
+    }
+
+    pub fn name(&self) -> Option<&'static str> {
+        Self::NAMES
+            .iter()
+            .find(|(_, b)| b == self)
+            .map(|&(name, _)| name)
+    }
+}
+
+#[derive(Resource, Debug)]
+pub struct InputMap {
+    bindings: Vec<(Binding, Action)>,
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        let bindings = [
+            ("w", Action::Forward),
+            ("s", Action::Backward),
+            ("a", Action::Left),
+            ("d", Action::Right),
+            ("e", Action::Up),
+            ("c", Action::Down),
+            ("lctrl", Action::Crouch),
+            ("lshift", Action::Sneak),
+            ("space", Action::Jump),
+            ("mouse1", Action::Fire),
+            ("r", Action::Reload),
+            ("f", Action::Use),
+            ("1", Action::Weapon1),
+            ("2", Action::Weapon2),
+            ("3", Action::Weapon3),
+            ("4", Action::Weapon4),
+            ("tab", Action::Scoreboard),
+            ("lstick_up", Action::Forward),
+            ("lstick_down", Action::Backward),
+            ("lstick_left", Action::Left),
+            ("lstick_right", Action::Right),
+            ("rstick_left", Action::LookLeft),
+            ("rstick_right", Action::LookRight),
+            ("rstick_up", Action::LookUp),
+            ("rstick_down", Action::LookDown),
+            ("pad_rb", Action::Up),
+            ("pad_lb", Action::Down),
+            ("pad_b", Action::Crouch),
+            ("pad_ls", Action::Sneak),
+            ("pad_a", Action::Jump),
+            ("pad_rt", Action::Fire),
+            ("pad_x", Action::Reload),
+            ("pad_y", Action::Use),
+            ("pad_select", Action::Scoreboard),
+        ]
+        .into_iter()
+        .map(|(name, action)| (Binding::from_name(name).unwrap(), action))
+        .collect();
+
+        Self { bindings }
+    }
+}
+
+impl InputMap {
+    /// Binds an input to an action, replacing any previous action of that input.
+    pub fn bind(&mut self, binding: Binding, action: Action) {
+        self.unbind(binding);
+        self.bindings.push((binding, action));
+    }
+
+    pub fn unbind(&mut self, binding: Binding) {
+        self.bindings.retain(|&(b, _)| b != binding);
+    }
+
+    pub fn clear(&mut self) {
+        self.bindings.clear();
+    }
+}
+
+/// Current state of all [Action]s, combined from their bindings.
+#[derive(Resource, Debug, Default)]
+pub struct ActionState {
+    buttons: ButtonInput<Action>,
+    values: Vec<(Action, f32)>,
+}
+
+impl ActionState {
+    /// Inputs count as pressed once they are pushed past this value.
+    const PRESS_THRESHOLD: f32 = 0.5;
+
+    pub fn pressed(&self, action: Action) -> bool {
+        self.buttons.pressed(action)
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.buttons.just_pressed(action)
+    }
+
+    pub fn just_released(&self, action: Action) -> bool {
+        self.buttons.just_released(action)
+    }
+
+    /// Analog value of the action in the range `[0, 1]`.
+    pub fn value(&self, action: Action) -> f32 {
+        self.values
+            .iter()
+            .filter(|&&(a, _)| a == action)
+            .map(|&(_, value)| value)
+            .fold(0.0, f32::max)
+    }
+
+    /// Difference of two opposing actions, in the range `[-1, 1]`.
+    pub fn axis(&self, positive: Action, negative: Action) -> f32 {
+        self.value(positive) - self.value(negative)
+    }
+}
+
+fn update_action_state(
+    map: Res<InputMap>,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    gamepads: Query<&Gamepad>,
+    mut state: ResMut<ActionState>,
+) {
+    let value = |binding: Binding| -> f32 {
+        match binding {
+            Binding::Key(key) => keyboard.pressed(key) as u8 as f32,
+            Binding::Mouse(button) => mouse.pressed(button) as u8 as f32,
+            Binding::GamepadButton(button) => gamepads
+                .iter()
+                .map(|gamepad| {
+                    let pressed = gamepad.pressed(button) as u8 as f32;
+                    gamepad.get(button).unwrap_or(pressed)
+                })
+                .fold(0.0, f32::max),
+            Binding::GamepadAxis { axis, positive } => gamepads
+                .iter()
+                .filter_map(|gamepad| gamepad.get(axis))
+                .map(|value| if positive { value } else { -value })
+                .fold(0.0, f32::max),
+        }
+    };
+
+    let tapped = |binding: Binding| -> bool {
+        match binding {
+            Binding::Key(key) => keyboard.just_pressed(key),
+            Binding::Mouse(button) => mouse.just_pressed(button),
+            Binding::GamepadButton(button) => gamepads.iter().any(|g| g.just_pressed(button)),
+            Binding::GamepadAxis { .. } => false,
+        }
+    };
+    let tapped = map
+        .bindings
+        .iter()
+        .filter(|&&(binding, _)| tapped(binding))
+        .map(|&(_, action)| action)
+        .collect::<Vec<_>>();
+
+    let state = &mut *state;
+    state.values = map
+        .bindings
+        .iter()
+        .map(|&(binding, action)| (action, value(binding)))
+        .collect();
+
+    state.buttons.clear();
+    for &(_, action) in Action::NAMES {
+        if state.value(action) > ActionState::PRESS_THRESHOLD {
+            state.buttons.press(action);
+        } else {
+            // presses shorter than a frame still count as just pressed
+            if tapped.contains(&action) {
+                state.buttons.press(action);
+            }
+            state.buttons.release(action);
+        }
+    }
+}
+
+fn bind(In(args): Args, mut map: ResMut<InputMap>) {
+    let (Some(binding), Some(action)) = (args.get(1), args.get(2)) else {
+        warn!("Usage: bind <input> <action>");
+        return;
+    };
+    let Some(binding) = Binding::from_name(binding) else {
+        warn!("Unknown input {binding}");
+        return;
+    };
+    let Some(action) = Action::from_name(action) else {
+        warn!("Unknown action {action}");
+        return;
+    };
+    map.bind(binding, action);
+}
+
+fn unbind(In(args): Args, mut map: ResMut<InputMap>) {
+    let Some(binding) = args.get(1) else {
+        warn!("Usage: unbind <input>");
+        return;
+    };
+    let Some(binding) = Binding::from_name(binding) else {
+        warn!("Unknown input {binding}");
+        return;
+    };
+    map.unbind(binding);
+}
+
+fn unbind_all(_args: Args, mut map: ResMut<InputMap>) {
+    map.clear();
+}
+
+fn load_bindings(mut evw: EventWriter<CommandEvent>) {
+    if fs::exists(BINDINGS_PATH).unwrap_or(false) {
+        evw.write(CommandEvent::new("exec", vec![BINDINGS_PATH]));
+    }
+}
+
+fn save_bindings(map: Res<InputMap>) {
+    if !map.is_changed() || map.is_added() {
+        return;
+    }
+
+    let mut config = String::from("unbindall\n");
+    for (binding, action) in map.bindings.iter() {
+        if let Some(name) = binding.name() {
+            config += &format!("bind {name} {}\n", action.name());
+        }
+    }
+
+    if let Err(err) = fs::write(BINDINGS_PATH, config) {
+        warn!("Could not save bindings: {err}");
+    }
+}
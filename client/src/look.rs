@@ -0,0 +1,249 @@
+use crate::cursor::CursorState;
+use crate::input::{Action, ActionState};
+use crate::{ClientState, PlayerCommand};
+use bevy::input::mouse::MouseMotion;
+use bevy::prelude::*;
+use shared::console::CommandAppExt;
+
+/// Turns mouse and gamepad look inputs into view angles of the [PlayerCommand].
+pub struct LookPlugin;
+
+impl Plugin for LookPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LookSettings>()
+            .add_systems(
+                Update,
+                (handle_mouse_motion, handle_gamepad_look).run_if(in_state(ClientState::InGame)),
+            )
+            .add_cvar("sensitivity", |s: &mut LookSettings| &mut s.sensitivity)
+            .add_cvar("m_yaw", |s: &mut LookSettings| &mut s.yaw)
+            .add_cvar("m_pitch", |s: &mut LookSettings| &mut s.pitch)
+            .add_cvar("m_accel", |s: &mut LookSettings| &mut s.acceleration)
+            .add_cvar("m_accel_cap", |s: &mut LookSettings| {
+                &mut s.acceleration_cap
+            })
+            .add_cvar("m_invert", |s: &mut LookSettings| &mut s.invert)
+            .add_cvar("m_fovscale", |s: &mut LookSettings| &mut s.fov_scaling);
+    }
+}
+
+#[derive(Resource, Debug, Clone)]
+pub struct LookSettings {
+    /// Overall multiplier applied to both axes.
+    pub sensitivity: f32,
+    /// Degrees turned horizontally per mouse count, before sensitivity.
+    pub yaw: f32,
+    /// Degrees turned vertically per mouse count, before sensitivity.
+    pub pitch: f32,
+    /// Extra sensitivity per count per millisecond of mouse speed, `0` disables acceleration.
+    pub acceleration: f32,
+    /// Upper bound of the sensitivity added by acceleration, `0` means no bound.
+    pub acceleration_cap: f32,
+    pub invert: bool,
+    /// Scale sensitivity with the field of view relative to [LookSettings::reference_fov], so zooming in slows turning down.
+    pub fov_scaling: bool,
+    /// Vertical field of view in radians at which no scaling happens.
+    pub reference_fov: f32,
+    /// Turn rate of a fully deflected gamepad stick, in radians per second.
+    pub gamepad_speed: Vec2,
+}
+
+impl Default for LookSettings {
+    fn default() -> Self {
+        Self {
+            sensitivity: 2.5,
+            yaw: 0.02,
+            pitch: 0.02,
+            acceleration: 0.0,
+            acceleration_cap: 0.0,
+            invert: false,
+            fov_scaling: true,
+            reference_fov: crate::DEFAULT_FOV,
+            gamepad_speed: Vec2::new(3.0, 2.0),
+        }
+    }
+}
+
+impl LookSettings {
+    /// Converts mouse counts moved during `delta_secs` to a yaw and pitch delta in radians.
+    pub fn mouse_angles(&self, counts: Vec2, delta_secs: f32, fov: f32) -> Vec2 {
+        let mut sensitivity = self.sensitivity;
+
+        if self.acceleration > 0.0 && delta_secs > 0.0 {
+            let speed = counts.length() / (delta_secs * 1000.0);
+            let mut extra = speed * self.acceleration;
+            if self.acceleration_cap > 0.0 {
+                extra = extra.min(self.acceleration_cap);
+            }
+            sensitivity += extra;
+        }
+
+        if self.fov_scaling {
+            sensitivity *= self.fov_ratio(fov);
+        }
+
+        let mut degrees = -counts * sensitivity * Vec2::new(self.yaw, self.pitch);
+        if self.invert {
+            degrees.y = -degrees.y;
+        }
+        degrees.map(f32::to_radians)
+    }
+
+    /// Converts stick deflection in the range `[-1, 1]` to a yaw and pitch delta in radians.
+    pub fn gamepad_angles(&self, stick: Vec2, delta_secs: f32, fov: f32) -> Vec2 {
+        let mut angles = stick * self.gamepad_speed * delta_secs;
+        if self.fov_scaling {
+            angles *= self.fov_ratio(fov);
+        }
+        if self.invert {
+            angles.y = -angles.y;
+        }
+        angles
+    }
+
+    fn fov_ratio(&self, fov: f32) -> f32 {
+        (fov / 2.0).tan() / (self.reference_fov / 2.0).tan()
+    }
+}
+
+/// Field of view of the active camera, used to scale look sensitivity.
+fn active_fov(q_camera: &Query<(&Camera, &Projection)>, settings: &LookSettings) -> f32 {
+    q_camera
+        .iter()
+        .filter(|(camera, _)| camera.is_active)
+        .find_map(|(_, projection)| match projection {
+            Projection::Perspective(perspective) => Some(perspective.fov),
+            _ => None,
+        })
+        .unwrap_or(settings.reference_fov)
+}
+
+fn handle_mouse_motion(
+    mut evr_mouse: EventReader<MouseMotion>,
+    mut command: ResMut<PlayerCommand>,
+    settings: Res<LookSettings>,
+    cursor: Res<CursorState>,
+    q_camera: Query<(&Camera, &Projection)>,
+    time: Res<Time>,
+) {
+    // the mouse belongs to whatever released the cursor
+    if !cursor.grabbed() {
+        evr_mouse.clear();
+        return;
+    }
+
+    // accumulate raw counts first, so acceleration sees the speed of the whole frame
+    let counts = evr_mouse.read().map(|e| e.delta).sum::<Vec2>();
+    if counts != Vec2::ZERO {
+        let fov = active_fov(&q_camera, &settings);
+        command.look(settings.mouse_angles(counts, time.delta_secs(), fov));
+    }
+}
+
+fn handle_gamepad_look(
+    actions: Res<ActionState>,
+    mut command: ResMut<PlayerCommand>,
+    settings: Res<LookSettings>,
+    q_camera: Query<(&Camera, &Projection)>,
+    time: Res<Time>,
+) {
+    let stick = Vec2::new(
+        actions.axis(Action::LookLeft, Action::LookRight),
+        actions.axis(Action::LookUp, Action::LookDown),
+    );
+    if stick != Vec2::ZERO {
+        let fov = active_fov(&q_camera, &settings);
+        command.look(settings.gamepad_angles(stick, time.delta_secs(), fov));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::cursor::{CursorPlugin, UiLayer};
+    use bevy::state::app::StatesPlugin;
+    use bevy::window::PrimaryWindow;
+
+    fn app(settings: LookSettings) -> App {
+        let mut app = App::new();
+        app.add_plugins((StatesPlugin, CursorPlugin))
+            .insert_state(ClientState::InGame)
+            .add_event::<MouseMotion>()
+            .init_resource::<Time>()
+            .init_resource::<ButtonInput<KeyCode>>()
+            .init_resource::<ButtonInput<MouseButton>>()
+            .init_resource::<PlayerCommand>()
+            .insert_resource(settings)
+            .add_systems(
+                Update,
+                handle_mouse_motion.run_if(in_state(ClientState::InGame)),
+            );
+        app.world_mut().spawn((
+            Window {
+                focused: true,
+                ..Default::default()
+            },
+            PrimaryWindow,
+        ));
+        app
+    }
+
+    fn motion(app: &mut App, delta: Vec2) -> Vec2 {
+        app.world_mut().send_event(MouseMotion { delta });
+        app.update();
+        app.world().resource::<PlayerCommand>().angle
+    }
+
+    #[test]
+    fn mouse_motion_turns_view() {
+        let mut app = app(LookSettings {
+            sensitivity: 1.0,
+            yaw: 1.0,
+            pitch: 1.0,
+            ..Default::default()
+        });
+
+        let angle = motion(&mut app, Vec2::new(-10.0, 10.0));
+        assert!((angle.x - 10f32.to_radians()).abs() < 1e-5);
+        assert!((angle.y + 10f32.to_radians()).abs() < 1e-5);
+    }
+
+    #[test]
+    fn invert_flips_pitch() {
+        let mut app = app(LookSettings {
+            invert: true,
+            ..Default::default()
+        });
+
+        assert!(motion(&mut app, Vec2::new(0.0, 10.0)).y > 0.0);
+    }
+
+    #[test]
+    fn released_cursor_ignores_motion() {
+        let mut app = app(LookSettings::default());
+        app.world_mut().spawn(UiLayer);
+
+        assert_eq!(motion(&mut app, Vec2::new(10.0, 10.0)), Vec2::ZERO);
+    }
+
+    #[test]
+    fn fov_scaling_slows_zoomed_view() {
+        let settings = LookSettings::default();
+        let counts = Vec2::new(10.0, 0.0);
+        let zoomed = settings.mouse_angles(counts, 0.016, settings.reference_fov / 2.0);
+        let normal = settings.mouse_angles(counts, 0.016, settings.reference_fov);
+        assert!(zoomed.x.abs() < normal.x.abs());
+    }
+
+    #[test]
+    fn acceleration_speeds_up_fast_motion() {
+        let settings = LookSettings {
+            acceleration: 0.5,
+            ..Default::default()
+        };
+        let slow = settings.mouse_angles(Vec2::new(10.0, 0.0), 0.016, settings.reference_fov);
+        let fast = settings.mouse_angles(Vec2::new(100.0, 0.0), 0.016, settings.reference_fov);
+        assert!(fast.x.abs() > slow.x.abs() * 10.0);
+    }
+}
@@ -1,134 +1,326 @@
-use crate::command::CommandPlugin;
+use crate::bodies::BodyPlugin;
+use crate::bot::BotOptions;
+use crate::combat::CombatPlugin;
+use crate::console::ConsolePlugin;
+use crate::cursor::CursorPlugin;
+use crate::flags::FlagPlugin;
+use crate::input::{Action, ActionState, InputPlugin};
+use crate::look::LookPlugin;
+use crate::maps::MapPlugin;
+use crate::menu::MenuPlugin;
+use crate::movers::MoverPlugin;
 use crate::net::NetPlugin;
-use bevy::input::mouse::MouseMotion;
+use crate::projectiles::ProjectilePlugin;
+use crate::scoreboard::ScoreboardPlugin;
+use crate::settings::SettingsPlugin;
+use bevy::log::LogPlugin;
 use bevy::prelude::*;
-use bevy::window::{CursorGrabMode, PrimaryWindow, WindowMode};
+use server::ServerPlugin;
+use server::command::{CommandBuffer, consume_commands};
+use shared::console::{Args, CommandAppExt};
+use shared::consts::{PITCH_LIMIT, asset_path};
 use shared::interpolate::InterpolateRotation;
-use shared::pawns::fly::{FlyPawn, FlyPawnCommand};
-use shared::pawns::fps::{FirstPersonPawn, FirstPersonPawnCommand};
+use shared::pawns::fly::FlyPawn;
+use shared::pawns::fps::{EYE_OFFSET, FirstPersonPawn};
+use shared::pawns::spectator::Spectator;
+use shared::pawns::{Controller, PawnControlSystems, PossessExt, Possesses};
 use shared::plugins::SharedPlugins;
+use shared::{InputEvent, InputKind};
+use std::env;
+use std::f32::consts::TAU;
 
-mod command;
+mod bodies;
+mod bot;
+mod combat;
+mod console;
+mod cursor;
+mod flags;
+mod input;
+mod look;
+mod maps;
+mod menu;
+mod movers;
 mod net;
+mod projectiles;
+mod scoreboard;
+mod settings;
+
+fn main() -> AppExit {
+    if let Some(options) = BotOptions::from_args(env::args()) {
+        return bot::run(options);
+    }
 
-fn main() {
     App::new()
-        .add_plugins(DefaultPlugins.set(WindowPlugin {
-            primary_window: Some(Window {
-                mode: WindowMode::BorderlessFullscreen(MonitorSelection::Primary),
-                focused: true,
-                ..Default::default()
-            }),
-            ..Default::default()
-        }))
+        .add_plugins(
+            DefaultPlugins
+                .set(WindowPlugin {
+                    primary_window: Some(Window {
+                        focused: true,
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                })
+                .set(AssetPlugin {
+                    file_path: asset_path(),
+                    ..Default::default()
+                })
+                .set(LogPlugin {
+                    custom_layer: console::log_layer,
+                    ..Default::default()
+                }),
+        )
         .add_plugins(SharedPlugins)
-        .add_plugins(CommandPlugin)
+        .add_plugins(InputPlugin)
+        .add_plugins(CursorPlugin)
+        .add_plugins(ConsolePlugin)
+        .add_plugins(LookPlugin)
+        .add_plugins(MenuPlugin)
+        .add_plugins(SettingsPlugin)
         .add_plugins(NetPlugin)
+        .add_plugins(CombatPlugin)
+        .add_plugins(ProjectilePlugin)
+        .add_plugins(ScoreboardPlugin)
+        .add_plugins(FlagPlugin)
+        .add_plugins(MapPlugin)
+        .add_plugins(BodyPlugin)
+        .add_plugins(MoverPlugin)
+        .add_plugins(ServerPlugin)
+        .init_state::<ClientState>()
+        .add_systems(Startup, startup)
         .add_systems(
-            Startup,
-            (shared::scenes::example::setup, startup, cursor_grab),
+            Update,
+            (
+                handle_button_inputs.run_if(in_state(ClientState::InGame)),
+                activate_possessed_camera,
+            ),
         )
-        .add_systems(Update, (handle_mouse_motion, handle_button_inputs))
         .add_systems(
             FixedPreUpdate,
-            (control_fly_pawn, control_first_person_pawn),
+            control_local_player
+                .before(PawnControlSystems)
+                .before(consume_commands)
+                .run_if(in_state(ClientState::InGame)),
+        )
+        .add_systems(OnExit(ClientState::InGame), release_command)
+        .add_systems(
+            FixedUpdate,
+            apply_view_angles.after(shared::pawns::fps::simulate_system),
         )
         .add_systems(FixedPostUpdate, clear_command)
         .init_resource::<PlayerCommand>()
-        .run();
+        .add_command("spectate", spectate)
+        .add_command("play", play)
+        .run()
+}
+
+#[derive(States, Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[states(scoped_entities)]
+enum ClientState {
+    #[default]
+    MainMenu,
+    Connecting,
+    InGame,
+    /// Still connected and simulating, but inputs go to the pause menu.
+    Paused,
 }
 
 #[derive(Debug, Resource, Default, Deref, DerefMut)]
 struct PlayerCommand(shared::Command);
 
+impl PlayerCommand {
+    /// Turns the view by the given yaw and pitch delta, in radians.
+    fn look(&mut self, delta: Vec2) {
+        self.angle.x = (self.angle.x + delta.x).rem_euclid(TAU);
+        self.angle.y = (self.angle.y + delta.y).clamp(-PITCH_LIMIT, PITCH_LIMIT);
+    }
+}
+
+/// The [Controller] driven by this client's inputs, along with the pawns it can possess.
+/// While hosting, it also carries a [CommandBuffer] and is fed like any remote client.
+#[derive(Component)]
+#[require(Controller)]
+struct LocalPlayer {
+    pawn: Entity,
+    spectator: Entity,
+}
+
 fn startup(mut commands: Commands) {
-    // commands.spawn((
-    //     FlyPawn { speed: 10.0 },
-    //     // Camera3d::default(),
-    //     // Camera {
-    //     //     is_active: true,
-    //     //     ..Default::default()
-    //     // },
-    //     // PerspectiveProjection {
-    //     //     fov: 90.0,
-    //     //     ..Default::default()
-    //     // },
-    //     Transform::from_xyz(5.0, 5.0, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
-    // ));
+    let spectator = commands
+        .spawn((
+            FlyPawn { speed: 10.0 },
+            Spectator::default(),
+            Transform::from_xyz(5.0, 5.0, 5.0),
+        ))
+        .with_child(camera())
+        .id();
 
-    commands
+    let pawn = commands
         .spawn(FirstPersonPawn::default())
-        .with_children(|spawner| {
-            spawner.spawn((
-                Camera3d::default(),
-                Camera {
-                    is_active: true,
-                    ..Default::default()
-                },
-                Projection::Perspective(PerspectiveProjection {
-                    fov: 90.0,
-                    ..Default::default()
-                }),
-                InterpolateRotation::default(),
-                Transform::from_xyz(0.0, 0.5, 0.0),
-            ));
-        });
+        .with_child((
+            camera(),
+            InterpolateRotation::default(),
+            Transform::from_translation(EYE_OFFSET),
+        ))
+        .id();
+
+    // joining players start out spectating
+    commands
+        .spawn(LocalPlayer { pawn, spectator })
+        .possess(spectator);
 }
 
-fn control_first_person_pawn(
-    command: Res<PlayerCommand>,
-    mut q: Query<&mut FirstPersonPawnCommand, With<FirstPersonPawn>>,
+/// Vertical field of view of player cameras in radians, until settings are applied.
+const DEFAULT_FOV: f32 = std::f32::consts::FRAC_PI_2;
+
+fn camera() -> impl Bundle {
+    (
+        Camera3d::default(),
+        Camera {
+            is_active: false,
+            ..Default::default()
+        },
+        Projection::Perspective(PerspectiveProjection {
+            fov: DEFAULT_FOV,
+            ..Default::default()
+        }),
+    )
+}
+
+fn spectate(_args: Args, mut commands: Commands, player: Single<(Entity, &LocalPlayer)>) {
+    let (entity, player) = *player;
+    commands.entity(entity).possess(player.spectator);
+}
+
+fn play(_args: Args, mut commands: Commands, player: Single<(Entity, &LocalPlayer)>) {
+    let (entity, player) = *player;
+    commands.entity(entity).possess(player.pawn);
+}
+
+/// Only the camera attached to the locally possessed pawn renders.
+fn activate_possessed_camera(
+    player: Single<&Possesses, With<LocalPlayer>>,
+    mut q_camera: Query<(&mut Camera, &ChildOf)>,
 ) {
-    q.iter_mut().for_each(|mut c| c.apply(&command));
+    for (mut camera, child_of) in q_camera.iter_mut() {
+        let active = child_of.parent() == player.0;
+        if camera.is_active != active {
+            camera.is_active = active;
+        }
+    }
 }
 
-fn control_fly_pawn(command: Res<PlayerCommand>, mut q: Query<&mut FlyPawnCommand, With<FlyPawn>>) {
-    q.iter_mut().for_each(|mut c| c.apply(&command));
+fn control_local_player(
+    command: Res<PlayerCommand>,
+    player: Single<(&mut Controller, Option<&mut CommandBuffer>), With<LocalPlayer>>,
+) {
+    submit(player.into_inner(), command.0.clone());
 }
 
-const SENSITIVITY: Vec2 = Vec2::new(0.05, 0.05);
+/// Hands a command to the local controller, through the server's buffer when hosting.
+fn submit(player: (Mut<Controller>, Option<Mut<CommandBuffer>>), command: shared::Command) {
+    match player {
+        (_, Some(mut buffer)) => buffer.push(command),
+        (mut controller, None) => controller.0 = command,
+    }
+}
 
-fn cursor_grab(mut q_windows: Query<&mut Window, With<PrimaryWindow>>) {
-    let mut primary_window = q_windows.single_mut().unwrap();
-    primary_window.cursor_options.grab_mode = CursorGrabMode::Locked;
-    primary_window.cursor_options.visible = false;
+/// The simulation only tracks view angles on the pawn, the camera merely follows them.
+fn apply_view_angles(
+    q_pawn: Query<(&FirstPersonPawn, &Children)>,
+    mut q_camera: Query<&mut Transform, With<Camera3d>>,
+) {
+    for (pawn, children) in q_pawn.iter() {
+        let mut cameras = q_camera.iter_many_mut(children);
+        while let Some(mut camera) = cameras.fetch_next() {
+            camera.rotation = pawn.view_rotation();
+        }
+    }
 }
 
-fn handle_mouse_motion(
-    mut evr_mouse: EventReader<MouseMotion>,
+/// Lets go of all buttons when inputs stop reaching the pawn, only the view direction is kept.
+fn release_command(
     mut command: ResMut<PlayerCommand>,
+    player: Single<(&mut Controller, Option<&mut CommandBuffer>), With<LocalPlayer>>,
 ) {
-    for MouseMotion { delta } in evr_mouse.read() {
-        command.angle.x += (delta.x * SENSITIVITY.x).to_radians();
-        command.angle.y += (delta.y * SENSITIVITY.y).to_radians();
-    }
+    command.0 = shared::Command {
+        angle: command.angle,
+        ..Default::default()
+    };
+    submit(player.into_inner(), command.0.clone());
 }
 
 fn clear_command(mut command: ResMut<PlayerCommand>) {
-    command.angle = Vec2::ZERO;
     command.jump = false;
     command.fire = false;
+    command.reload = false;
+    command.interact = false;
+    command.weapon = None;
+    command.events.clear();
 }
 
 fn handle_button_inputs(
-    keyboard: Res<ButtonInput<KeyCode>>,
-    mouse: Res<ButtonInput<MouseButton>>,
+    actions: Res<ActionState>,
     mut command: ResMut<PlayerCommand>,
+    time: Res<Time<Fixed>>,
 ) {
-    command.forward = keyboard.pressed(KeyCode::KeyW);
-    command.backward = keyboard.pressed(KeyCode::KeyS);
-    command.left = keyboard.pressed(KeyCode::KeyA);
-    command.right = keyboard.pressed(KeyCode::KeyD);
-    command.up = keyboard.pressed(KeyCode::KeyE);
-    command.down = keyboard.pressed(KeyCode::KeyC);
-    command.crouch = keyboard.pressed(KeyCode::ControlLeft);
-    command.sneak = keyboard.pressed(KeyCode::ShiftLeft);
+    command.forward = actions.pressed(Action::Forward);
+    command.backward = actions.pressed(Action::Backward);
+    command.left = actions.pressed(Action::Left);
+    command.right = actions.pressed(Action::Right);
+    command.up = actions.pressed(Action::Up);
+    command.down = actions.pressed(Action::Down);
+    command.crouch = actions.pressed(Action::Crouch);
+    command.sneak = actions.pressed(Action::Sneak);
 
     if !command.jump {
-        command.jump = keyboard.just_pressed(KeyCode::Space);
+        command.jump = actions.just_pressed(Action::Jump);
     }
 
     if !command.fire {
-        command.fire = mouse.pressed(MouseButton::Left);
+        command.fire = actions.pressed(Action::Fire) || actions.just_pressed(Action::Fire);
+    }
+
+    if !command.reload {
+        command.reload = actions.just_pressed(Action::Reload);
+    }
+
+    if !command.interact {
+        command.interact = actions.just_pressed(Action::Use);
+    }
+
+    for (slot, action) in [
+        Action::Weapon1,
+        Action::Weapon2,
+        Action::Weapon3,
+        Action::Weapon4,
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        if actions.just_pressed(action) {
+            command.weapon = Some(slot as u8);
+        }
+    }
+
+    // inputs gathered now are simulated in the next tick, stamp them with how far into it they happened
+    let tick_time = time.overstep_fraction().min(1.0);
+    for (action, kind) in [
+        (Action::Jump, InputKind::Jump),
+        (Action::Fire, InputKind::Fire),
+    ] {
+        if actions.just_pressed(action) {
+            command.events.push(InputEvent {
+                kind,
+                pressed: true,
+                time: tick_time,
+            });
+        }
+        if actions.just_released(action) {
+            command.events.push(InputEvent {
+                kind,
+                pressed: false,
+                time: tick_time,
+            });
+        }
     }
 }
@@ -0,0 +1,128 @@
+use crate::menu::MenuMessage;
+use crate::net::ReceivedMessage;
+use bevy::gltf::{GltfMesh, GltfNode};
+use bevy::prelude::*;
+use server::net::is_listening;
+use shared::console::CommandEvent;
+use shared::maps::{CurrentMap, LoadMapEvent, MapFailedEvent, MapLight, MapNode};
+use shared::protocol::ServerMessage;
+
+/// Loads the map the server plays on, and shows maps with their meshes and lights.
+pub struct MapPlugin;
+
+impl Plugin for MapPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ExpectedMap>().add_systems(
+            Update,
+            (
+                (follow_server_map, verify_map)
+                    .chain()
+                    .run_if(not(is_listening)),
+                add_map_visuals,
+            ),
+        );
+    }
+}
+
+/// Map the server told us to load, until ours is loaded and its hash compared.
+#[derive(Resource, Debug, Default)]
+struct ExpectedMap(Option<(String, u64)>);
+
+fn follow_server_map(
+    mut evr: EventReader<ReceivedMessage>,
+    mut evw_load: EventWriter<LoadMapEvent>,
+    mut expected: ResMut<ExpectedMap>,
+    current: Res<CurrentMap>,
+) {
+    for ReceivedMessage(message) in evr.read() {
+        let ServerMessage::Map { name, hash } = message else {
+            continue;
+        };
+        if current.name != *name {
+            evw_load.write(LoadMapEvent(name.clone()));
+        }
+        expected.0 = Some((name.clone(), *hash));
+    }
+}
+
+/// Leaves servers which play a map we cannot load, or a different version of it than ours.
+fn verify_map(
+    mut evw_command: EventWriter<CommandEvent>,
+    mut evr_failed: EventReader<MapFailedEvent>,
+    mut expected: ResMut<ExpectedMap>,
+    mut message: ResMut<MenuMessage>,
+    current: Res<CurrentMap>,
+) {
+    for MapFailedEvent(failed) in evr_failed.read() {
+        if expected.0.as_ref().is_some_and(|(name, _)| name == failed) {
+            evw_command.write(CommandEvent::new("disconnect", vec![]));
+            message.0 = format!("Could not load map {failed}, which the server plays");
+            expected.0 = None;
+        }
+    }
+
+    let Some(((name, hash), ours)) = expected.0.as_ref().zip(current.hash) else {
+        return;
+    };
+    if current.name != *name {
+        return;
+    }
+
+    if ours != *hash {
+        warn!("Map {name} is {ours:016x} here, but {hash:016x} on the server");
+        evw_command.write(CommandEvent::new("disconnect", vec![]));
+        message.0 = format!("Your copy of map {name} differs from the server's");
+    }
+    expected.0 = None;
+}
+
+fn add_map_visuals(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    nodes: Res<Assets<GltfNode>>,
+    meshes: Res<Assets<GltfMesh>>,
+    q_nodes: Query<(Entity, &MapNode, Option<&MapLight>), Added<MapNode>>,
+    mut default_material: Local<Option<Handle<StandardMaterial>>>,
+) {
+    for (entity, MapNode { node, .. }, light) in q_nodes.iter() {
+        let mut entity = commands.entity(entity);
+        entity.insert(Visibility::default());
+        match light {
+            Some(&MapLight::Directional {
+                illuminance,
+                shadows,
+            }) => {
+                entity.insert(DirectionalLight {
+                    illuminance,
+                    shadows_enabled: shadows,
+                    ..Default::default()
+                });
+            }
+            Some(&MapLight::Point { intensity, range }) => {
+                entity.insert(PointLight {
+                    intensity,
+                    range,
+                    shadows_enabled: true,
+                    ..Default::default()
+                });
+            }
+            None => {}
+        }
+
+        let Some(mesh) = nodes
+            .get(node)
+            .and_then(|node| node.mesh.as_ref())
+            .and_then(|mesh| meshes.get(mesh))
+        else {
+            continue;
+        };
+        for primitive in &mesh.primitives {
+            let material = primitive.material.clone().unwrap_or_else(|| {
+                default_material
+                    .get_or_insert_with(|| materials.add(StandardMaterial::default()))
+                    .clone()
+            });
+            entity.with_child((Mesh3d(primitive.mesh.clone()), MeshMaterial3d(material)));
+        }
+    }
+}
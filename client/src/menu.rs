@@ -0,0 +1,241 @@
+use crate::ClientState;
+use crate::cursor::{CursorState, UiLayer};
+use bevy::color::palettes::css::{RED, SILVER};
+use bevy::input::keyboard::{Key, KeyboardInput};
+use bevy::prelude::*;
+use shared::console::{Args, CommandAppExt, CommandEvent};
+use shared::consts::GAME_PORT;
+
+/// Main menu, connection screen and pause menu.
+pub struct MenuPlugin;
+
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ServerAddress>()
+            .init_resource::<MenuMessage>()
+            .add_systems(OnEnter(ClientState::MainMenu), spawn_main_menu)
+            .add_systems(OnEnter(ClientState::Connecting), spawn_connecting)
+            .add_systems(OnEnter(ClientState::Paused), spawn_pause_menu)
+            .add_systems(OnEnter(ClientState::InGame), recapture_cursor)
+            .add_systems(
+                Update,
+                (
+                    handle_menu_buttons,
+                    (edit_address, update_message).run_if(in_state(ClientState::MainMenu)),
+                    toggle_pause
+                        .run_if(in_state(ClientState::InGame).or(in_state(ClientState::Paused))),
+                ),
+            )
+            .add_command("quit", quit);
+    }
+}
+
+/// Address typed into the main menu.
+#[derive(Resource)]
+pub struct ServerAddress(pub String);
+
+impl Default for ServerAddress {
+    fn default() -> Self {
+        Self(format!("localhost:{GAME_PORT}"))
+    }
+}
+
+/// Shown in the main menu, e.g. why the last connection failed.
+#[derive(Resource, Default)]
+pub struct MenuMessage(pub String);
+
+#[derive(Component, Clone, Copy)]
+enum MenuButton {
+    Connect,
+    Host,
+    Cancel,
+    Resume,
+    Disconnect,
+    Quit,
+}
+
+#[derive(Component)]
+struct AddressField;
+
+#[derive(Component)]
+struct MessageText;
+
+fn menu_root(state: ClientState) -> impl Bundle {
+    (
+        UiLayer,
+        StateScoped(state),
+        Node {
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            flex_direction: FlexDirection::Column,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            row_gap: Val::Px(12.0),
+            ..Default::default()
+        },
+        BackgroundColor(Color::BLACK.with_alpha(0.6)),
+    )
+}
+
+fn title(text: impl Into<String>) -> impl Bundle {
+    (
+        Text::new(text),
+        TextFont {
+            font_size: 48.0,
+            ..Default::default()
+        },
+    )
+}
+
+fn button(label: &str, action: MenuButton) -> impl Bundle {
+    (
+        Button,
+        action,
+        Node {
+            width: Val::Px(240.0),
+            padding: UiRect::all(Val::Px(8.0)),
+            justify_content: JustifyContent::Center,
+            ..Default::default()
+        },
+        BackgroundColor(Color::from(SILVER).with_alpha(0.2)),
+        children![Text::new(label)],
+    )
+}
+
+fn spawn_main_menu(mut commands: Commands, address: Res<ServerAddress>, message: Res<MenuMessage>) {
+    commands.spawn((
+        menu_root(ClientState::MainMenu),
+        children![
+            title("bevy_fps"),
+            (
+                MessageText,
+                Text::new(message.0.clone()),
+                TextColor(RED.into())
+            ),
+            (
+                AddressField,
+                Text::new(address.0.clone()),
+                Node {
+                    width: Val::Px(240.0),
+                    padding: UiRect::all(Val::Px(8.0)),
+                    ..Default::default()
+                },
+                BackgroundColor(Color::BLACK),
+            ),
+            button("Connect", MenuButton::Connect),
+            button("Host", MenuButton::Host),
+            button("Quit", MenuButton::Quit),
+        ],
+    ));
+}
+
+fn spawn_connecting(mut commands: Commands, address: Res<ServerAddress>) {
+    commands.spawn((
+        menu_root(ClientState::Connecting),
+        children![
+            title(format!("Connecting to {}", address.0)),
+            button("Cancel", MenuButton::Cancel),
+        ],
+    ));
+}
+
+fn spawn_pause_menu(mut commands: Commands) {
+    commands.spawn((
+        menu_root(ClientState::Paused),
+        children![
+            title("Paused"),
+            button("Resume", MenuButton::Resume),
+            button("Disconnect", MenuButton::Disconnect),
+            button("Quit", MenuButton::Quit),
+        ],
+    ));
+}
+
+fn handle_menu_buttons(
+    q: Query<(&Interaction, &MenuButton), Changed<Interaction>>,
+    address: Res<ServerAddress>,
+    mut evw: EventWriter<CommandEvent>,
+    mut next_state: ResMut<NextState<ClientState>>,
+) {
+    for (interaction, button) in q.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match button {
+            MenuButton::Connect => {
+                evw.write(CommandEvent::new("connect", vec![&address.0]));
+            }
+            MenuButton::Host => {
+                evw.write(CommandEvent::new("host", vec![]));
+            }
+            MenuButton::Cancel | MenuButton::Disconnect => {
+                evw.write(CommandEvent::new("disconnect", vec![]));
+            }
+            MenuButton::Resume => next_state.set(ClientState::InGame),
+            MenuButton::Quit => {
+                evw.write(CommandEvent::new("quit", vec![]));
+            }
+        }
+    }
+}
+
+fn edit_address(
+    mut evr_keyboard: EventReader<KeyboardInput>,
+    mut address: ResMut<ServerAddress>,
+    mut evw: EventWriter<CommandEvent>,
+    mut q_field: Query<&mut Text, With<AddressField>>,
+) {
+    for event in evr_keyboard.read() {
+        if !event.state.is_pressed() {
+            continue;
+        }
+        match &event.logical_key {
+            Key::Backspace => {
+                address.0.pop();
+            }
+            Key::Enter => {
+                evw.write(CommandEvent::new("connect", vec![&address.0]));
+            }
+            Key::Character(text) => address.0.extend(text.chars().filter(|c| !c.is_control())),
+            _ => {}
+        }
+    }
+
+    if address.is_changed() {
+        for mut text in q_field.iter_mut() {
+            text.0 = address.0.clone();
+        }
+    }
+}
+
+fn update_message(message: Res<MenuMessage>, mut q_text: Query<&mut Text, With<MessageText>>) {
+    if message.is_changed() {
+        for mut text in q_text.iter_mut() {
+            text.0 = message.0.clone();
+        }
+    }
+}
+
+fn toggle_pause(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    state: Res<State<ClientState>>,
+    mut next_state: ResMut<NextState<ClientState>>,
+) {
+    if !keyboard.just_pressed(KeyCode::Escape) {
+        return;
+    }
+    match state.get() {
+        ClientState::InGame => next_state.set(ClientState::Paused),
+        ClientState::Paused => next_state.set(ClientState::InGame),
+        _ => {}
+    }
+}
+
+/// Leaving a menu hands the mouse straight back to the game.
+fn recapture_cursor(mut cursor: ResMut<CursorState>) {
+    cursor.recapture();
+}
+
+fn quit(_args: Args, mut evw: EventWriter<AppExit>) {
+    evw.write(AppExit::Success);
+}
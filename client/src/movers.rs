@@ -0,0 +1,134 @@
+use crate::net::ReceivedMessage;
+use bevy::prelude::*;
+use bevy_quinnet::client::QuinnetClient;
+use server::net::is_listening;
+use shared::maps::MapNode;
+use shared::movers::{Mover, MoverPhase, MoverState};
+use shared::protocol::ServerMessage;
+use std::collections::HashMap;
+
+/// Takes over the state of movers from the server, from where on they move by themselves.
+/// Triggers are only checked by the server, so movers we touch or use start moving here once it
+/// tells us, after a round trip. The state it sends is advanced by half of that, to catch up.
+pub struct MoverPlugin;
+
+impl Plugin for MoverPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, receive_movers.run_if(not(is_listening)));
+    }
+}
+
+/// Seconds along the path, or of waiting, ours may be off from the server's before it is taken.
+/// Keeps the regular syncs from jerking movers which already move like the server's.
+const DRIFT_TOLERANCE: f32 = 0.1;
+
+fn receive_movers(
+    mut evr: EventReader<ReceivedMessage>,
+    client: Res<QuinnetClient>,
+    time: Res<Time<Fixed>>,
+    mut q_movers: Query<(&MapNode, &mut Mover)>,
+) {
+    // the server's state is half a round trip old by the time it arrives
+    let latency = client
+        .get_connection()
+        .and_then(|connection| connection.connection_stats())
+        .map_or(0.0, |stats| stats.path.rtt.as_secs_f32() / 2.0);
+    let tick = time.timestep().as_secs_f32();
+    let ticks = (latency / tick).round() as u32;
+
+    for ReceivedMessage(message) in evr.read() {
+        let ServerMessage::Movers(updates) = message else {
+            continue;
+        };
+        let states = updates
+            .iter()
+            .map(|update| (update.node as usize, update.state))
+            .collect::<HashMap<_, _>>();
+        for (node, mut mover) in q_movers.iter_mut() {
+            if let Some(&state) = states.get(&node.index) {
+                reconcile(&mut mover, state, ticks, tick);
+            }
+        }
+    }
+}
+
+/// Advances the server's state by the ticks it is behind, and takes it if ours drifted from it.
+fn reconcile(mover: &mut Mover, state: MoverState, ticks: u32, tick: f32) {
+    let mut server = Mover {
+        state,
+        ..mover.clone()
+    };
+    // by whole ticks, like movers always move
+    for _ in 0..ticks {
+        server.advance(tick);
+    }
+    if drifted(&mover.state, &server.state) {
+        mover.state = server.state;
+    }
+}
+
+fn drifted(ours: &MoverState, theirs: &MoverState) -> bool {
+    let phase_apart = match (ours.phase, theirs.phase) {
+        (MoverPhase::Waiting { left: a }, MoverPhase::Waiting { left: b }) => {
+            (a - b).abs() > DRIFT_TOLERANCE
+        }
+        (a, b) => a != b,
+    };
+    phase_apart || (ours.time - theirs.time).abs() > DRIFT_TOLERANCE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shared::movers::{Keyframe, MoverMode, MoverPath};
+
+    const TICK: f32 = 1.0 / 60.0;
+
+    fn platform() -> Mover {
+        Mover::new(
+            Transform::default(),
+            MoverPath {
+                keyframes: vec![
+                    Keyframe {
+                        time: 0.0,
+                        translation: Vec3::ZERO,
+                        rotation: Quat::IDENTITY,
+                    },
+                    Keyframe {
+                        time: 4.0,
+                        translation: Vec3::new(8.0, 0.0, 0.0),
+                        rotation: Quat::IDENTITY,
+                    },
+                ],
+                mode: MoverMode::Loop,
+                wait: 0.0,
+            },
+        )
+    }
+
+    #[test]
+    fn syncs_of_movers_in_step_change_nothing() {
+        let mut ours = platform();
+        ours.advance(1.0);
+        // the server's was where ours is, six ticks ago
+        let mut server = platform();
+        server.advance(1.0 - 6.0 * TICK);
+        // a little off, like rounding leaves it
+        ours.state.time += 0.01;
+
+        let before = ours.state;
+        reconcile(&mut ours, server.state, 6, TICK);
+        assert_eq!(ours.state, before);
+    }
+
+    #[test]
+    fn drifted_movers_take_the_server_state_ahead_by_the_latency() {
+        let mut ours = platform();
+        let mut server = platform();
+        server.advance(2.0);
+
+        reconcile(&mut ours, server.state, 6, TICK);
+        assert!((ours.state.time - (2.0 + 6.0 * TICK)).abs() < 1e-4);
+        assert_eq!(ours.state.phase, MoverPhase::Forward);
+    }
+}
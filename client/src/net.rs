@@ -1,12 +1,25 @@
-use crate::command::{Args, CommandAppExt, CommandEvent};
+use crate::menu::MenuMessage;
+use crate::{ClientState, LocalPlayer, PlayerCommand};
 use bevy::prelude::*;
+use bevy::time::common_conditions::on_timer;
 use bevy_quinnet::client::certificate::CertificateVerificationMode;
 use bevy_quinnet::client::connection::{
     ClientEndpointConfiguration, ConnectionEvent, ConnectionFailedEvent, ConnectionLostEvent,
+    ConnectionState,
 };
 use bevy_quinnet::client::{QuinnetClient, QuinnetClientPlugin};
-use bevy_quinnet::shared::channels::ChannelsConfiguration;
-use shared::consts::GAME_PORT;
+use bevy_quinnet::server::QuinnetServer;
+use server::command::CommandBuffer;
+use server::game::Score;
+use server::net::{listen, stop_listening};
+use shared::channels::{self, ClientConnectionExt, NetMessage};
+use shared::conditioner::{LinkConditioner, LinkQueue};
+use shared::console::{Args, CommandAppExt, CommandEvent};
+use shared::consts::{BUILD_HASH, HEARTBEAT_INTERVAL, PROTOCOL_VERSION};
+use shared::pawns::Possesses;
+use shared::pawns::fps::FirstPersonPawnCommand;
+use shared::protocol::{ClientMessage, Hello, ServerMessage};
+use shared::session::{Actor, Team};
 use std::net::{Ipv6Addr, SocketAddr, SocketAddrV6, ToSocketAddrs};
 
 pub struct NetPlugin;
@@ -14,63 +27,366 @@ pub struct NetPlugin;
 impl Plugin for NetPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins(QuinnetClientPlugin::default());
-        app.add_systems(Update, handle_client_events);
+        app.init_resource::<Incoming>()
+            .init_resource::<Profile>()
+            .init_resource::<NetSettings>()
+            .init_resource::<ServerSession>()
+            .add_event::<ReceivedMessage>();
+        app.add_systems(PreUpdate, receive_messages);
+        app.add_systems(
+            Update,
+            (
+                handle_client_events,
+                check_timeout,
+                send_heartbeat.run_if(on_timer(HEARTBEAT_INTERVAL)),
+            ),
+        );
+        app.add_systems(
+            FixedPreUpdate,
+            send_command.run_if(in_state(ClientState::InGame).or(in_state(ClientState::Paused))),
+        );
 
-        // Connect to localhost at startup
-        app.add_systems(Startup, |mut evw: EventWriter<CommandEvent>| {
-            evw.write(CommandEvent::new(
-                "connect",
-                vec![format!("localhost:{GAME_PORT}").as_str()],
-            ));
-        });
+        app.add_cvar("name", |p: &mut Profile| &mut p.name);
+        app.add_cvar("cl_timeout", |s: &mut NetSettings| &mut s.timeout);
+        app.add_command(
+            "team",
+            |In(args): Args, mut profile: ResMut<Profile>| match args.get(1).map(String::as_str) {
+                None => match profile.team {
+                    Some(team) => info!("team is {team}"),
+                    None => info!("team is auto"),
+                },
+                Some("auto") => profile.team = None,
+                Some(team) => match team.parse() {
+                    Ok(team) => profile.team = Some(team),
+                    Err(_) => warn!("Usage: team <auto|red|blue>"),
+                },
+            },
+        );
 
         app.add_command(
             "connect",
-            |In(args): Args, mut client: ResMut<QuinnetClient>| {
-                let server_addr = args[1].as_str();
+            |In(args): Args,
+             mut client: ResMut<QuinnetClient>,
+             mut session: ResMut<ServerSession>,
+             time: Res<Time<Real>>,
+             mut message: ResMut<MenuMessage>,
+             mut next_state: ResMut<NextState<ClientState>>| {
+                let Some(server_addr) = args.get(1) else {
+                    warn!("Usage: connect <address>");
+                    return;
+                };
 
-                let server_addr = server_addr
+                let Some(server_addr) = server_addr
                     .to_socket_addrs()
-                    .expect("Could not resolve server address")
-                    .next()
-                    .expect("Server address not valid");
-
-                client
-                    .open_connection(
-                        ClientEndpointConfiguration::from_addrs(
-                            server_addr,
-                            SocketAddr::V6(SocketAddrV6::new(Ipv6Addr::UNSPECIFIED, 0, 0, 0)),
-                        ),
-                        CertificateVerificationMode::SkipVerification,
-                        ChannelsConfiguration::default(),
-                    )
-                    .expect("Could not open connection");
+                    .ok()
+                    .and_then(|mut addrs| addrs.next())
+                else {
+                    message.0 = format!("Could not resolve {server_addr}");
+                    return;
+                };
+
+                let result = client.open_connection(
+                    ClientEndpointConfiguration::from_addrs(
+                        server_addr,
+                        SocketAddr::V6(SocketAddrV6::new(Ipv6Addr::UNSPECIFIED, 0, 0, 0)),
+                    ),
+                    CertificateVerificationMode::SkipVerification,
+                    channels::configuration(),
+                );
+
+                match result {
+                    Ok(_) => {
+                        // a token is only good for the server which handed it out
+                        if session.address != Some(server_addr) {
+                            session.token = None;
+                        }
+                        session.address = Some(server_addr);
+                        session.last_received = time.elapsed_secs_f64();
+                        next_state.set(ClientState::Connecting);
+                    }
+                    Err(err) => message.0 = format!("Could not connect: {err:?}"),
+                }
             },
         );
 
         app.add_command(
             "disconnect",
-            |_args: Args, mut client: ResMut<QuinnetClient>| {
+            |_args: Args,
+             mut client: ResMut<QuinnetClient>,
+             mut session: ResMut<ServerSession>,
+             server: Res<QuinnetServer>,
+             mut commands: Commands,
+             player: Single<Entity, With<LocalPlayer>>,
+             mut next_state: ResMut<NextState<ClientState>>| {
                 client.close_all_connections();
+                // leaving on purpose gives up the session
+                *session = ServerSession::default();
+                if server.is_listening() {
+                    commands.run_system_cached(stop_listening);
+                    commands
+                        .entity(*player)
+                        .remove::<(CommandBuffer, Score, Team)>();
+                }
+                next_state.set(ClientState::MainMenu);
             },
-        )
+        );
+
+        // runs the server in this process, the local player skips the network entirely
+        app.add_command(
+            "host",
+            |_args: Args,
+             mut server: ResMut<QuinnetServer>,
+             mut commands: Commands,
+             player: Single<Entity, With<LocalPlayer>>,
+             profile: Res<Profile>,
+             mut message: ResMut<MenuMessage>,
+             mut next_state: ResMut<NextState<ClientState>>| {
+                if let Err(err) = listen(&mut server) {
+                    message.0 = format!("Could not host: {err}");
+                    return;
+                }
+                let mut player = commands.entity(*player);
+                player.insert((
+                    CommandBuffer::default(),
+                    Score::default(),
+                    Name::new(profile.name.clone()),
+                ));
+                if let Some(team) = profile.team {
+                    player.insert(team);
+                }
+                message.0.clear();
+                next_state.set(ClientState::InGame);
+            },
+        );
+    }
+}
+
+/// How this player introduces themselves to servers.
+#[derive(Resource, Debug)]
+pub struct Profile {
+    pub name: String,
+    /// Team to ask for when joining, `None` to let the server decide.
+    pub team: Option<Team>,
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self {
+            name: "player".to_owned(),
+            team: None,
+        }
+    }
+}
+
+#[derive(Resource, Debug)]
+pub struct NetSettings {
+    /// Seconds without any message from the server after which the connection counts as lost.
+    pub timeout: f32,
+}
+
+impl Default for NetSettings {
+    fn default() -> Self {
+        Self { timeout: 10.0 }
+    }
+}
+
+/// The server this client talks to, and what it needs to resume its session there.
+#[derive(Resource, Debug, Default)]
+struct ServerSession {
+    address: Option<SocketAddr>,
+    /// Handed out by the server on joining.
+    token: Option<u64>,
+    /// Set while reconnecting after losing the connection, so it is only tried once.
+    resuming: bool,
+    /// Real time the last message from the server arrived at, in seconds.
+    last_received: f64,
+}
+
+fn hello(profile: &Profile) -> ClientMessage {
+    ClientMessage::Hello(Hello {
+        version: PROTOCOL_VERSION,
+        build: BUILD_HASH.to_owned(),
+        name: profile.name.clone(),
+        team: profile.team,
+    })
+}
+
+/// Sends the command of every tick to the server, which simulates it on our pawn there.
+/// While spectating, the pawn is let go of like here, keeping only its view angles.
+fn send_command(
+    mut client: ResMut<QuinnetClient>,
+    command: Res<PlayerCommand>,
+    player: Single<(&LocalPlayer, Option<&Possesses>)>,
+    q_pawns: Query<&FirstPersonPawnCommand>,
+) {
+    let Some(connection) = client.get_connection_mut() else {
+        return;
+    };
+    let (player, possesses) = *player;
+    let command = if possesses.is_some_and(|possesses| possesses.0 == player.pawn) {
+        command.0.clone()
+    } else {
+        shared::Command {
+            angle: q_pawns
+                .get(player.pawn)
+                .map_or(command.angle, |pawn| pawn.angle),
+            ..Default::default()
+        }
+    };
+    connection.send(ClientMessage::Command(command));
+}
+
+/// Game messages from the server, for whichever part of the client they concern.
+/// Messages about the connection itself are handled here and not passed on.
+#[derive(Event, Debug, Clone)]
+pub struct ReceivedMessage(pub ServerMessage);
+
+/// Messages received from the server, waiting for the [LinkConditioner] to let them through.
+#[derive(Resource, Default, Deref, DerefMut)]
+struct Incoming(LinkQueue<ServerMessage>);
+
+#[allow(clippy::too_many_arguments)]
+fn receive_messages(
+    mut client: ResMut<QuinnetClient>,
+    mut incoming: ResMut<Incoming>,
+    mut session: ResMut<ServerSession>,
+    profile: Res<Profile>,
+    conditioner: Res<LinkConditioner>,
+    time: Res<Time<Real>>,
+    mut message: ResMut<MenuMessage>,
+    mut next_state: ResMut<NextState<ClientState>>,
+    mut commands: Commands,
+    player: Single<&LocalPlayer>,
+    mut evw: EventWriter<ReceivedMessage>,
+) {
+    let now = time.elapsed_secs_f64();
+    if let Some(connection) = client.get_connection_mut() {
+        while let Some(message) = connection.receive::<ServerMessage>() {
+            session.last_received = now;
+            incoming.push(&conditioner, now, message.channel().reliable(), message);
+        }
+    }
+
+    while let Some(server_message) = incoming.pop(now) {
+        match server_message {
+            ServerMessage::Welcome { token, actor } => {
+                // our pawn stands in for the one the server simulates
+                commands.entity(player.pawn).insert(Actor::new(actor));
+                session.token = Some(token);
+                session.resuming = false;
+                message.0.clear();
+                next_state.set(ClientState::InGame);
+            }
+            ServerMessage::Rejected { reason } => {
+                info!("Rejected by server: {reason}");
+                message.0 = reason;
+                session.token = None;
+                client.close_all_connections();
+                next_state.set(ClientState::MainMenu);
+            }
+            ServerMessage::Expired => {
+                info!("Session expired, joining again");
+                session.token = None;
+                if let Some(connection) = client.get_connection_mut() {
+                    connection.send(hello(&profile));
+                }
+            }
+            // only bots report these for now
+            ServerMessage::Stats { .. } => {}
+            ServerMessage::Heartbeat => {}
+            message => {
+                evw.write(ReceivedMessage(message));
+            }
+        }
+    }
+}
+
+fn send_heartbeat(mut client: ResMut<QuinnetClient>) {
+    if let Some(connection) = client.get_connection_mut()
+        && matches!(connection.state(), ConnectionState::Connected)
+    {
+        connection.send(ClientMessage::Heartbeat);
+    }
+}
+
+/// Gives up on a server which went silent, even if the connection itself is still open.
+fn check_timeout(
+    mut client: ResMut<QuinnetClient>,
+    mut session: ResMut<ServerSession>,
+    settings: Res<NetSettings>,
+    time: Res<Time<Real>>,
+    mut message: ResMut<MenuMessage>,
+    mut next_state: ResMut<NextState<ClientState>>,
+    mut evw: EventWriter<CommandEvent>,
+) {
+    let connected = client
+        .get_connection()
+        .is_some_and(|connection| matches!(connection.state(), ConnectionState::Connected));
+    if !connected || time.elapsed_secs_f64() - session.last_received <= settings.timeout as f64 {
+        return;
+    }
+
+    info!("Server timed out");
+    client.close_all_connections();
+    lose_connection(&mut session, &mut message, &mut next_state, &mut evw);
+}
+
+/// Tries once to get back into the session, or returns to the main menu.
+fn lose_connection(
+    session: &mut ServerSession,
+    message: &mut MenuMessage,
+    next_state: &mut NextState<ClientState>,
+    evw: &mut EventWriter<CommandEvent>,
+) {
+    message.0 = "Connection lost".to_owned();
+    match session.address {
+        Some(address) if session.token.is_some() && !session.resuming => {
+            info!("Trying to resume session");
+            session.resuming = true;
+            evw.write(CommandEvent::new("connect", vec![&address.to_string()]));
+        }
+        _ => {
+            session.resuming = false;
+            next_state.set(ClientState::MainMenu);
+        }
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn handle_client_events(
     mut connection_events: EventReader<ConnectionEvent>,
     mut connection_failed_events: EventReader<ConnectionFailedEvent>,
     mut connection_lost_events: EventReader<ConnectionLostEvent>,
+    mut client: ResMut<QuinnetClient>,
+    mut session: ResMut<ServerSession>,
+    profile: Res<Profile>,
+    time: Res<Time<Real>>,
+    mut message: ResMut<MenuMessage>,
+    mut next_state: ResMut<NextState<ClientState>>,
+    mut evw: EventWriter<CommandEvent>,
 ) {
     for ev in connection_events.read() {
         info!("Connected to server as {}", ev.client_id.unwrap());
+        session.last_received = time.elapsed_secs_f64();
+        // the game starts once the server accepts the handshake
+        let handshake = match session.token {
+            Some(token) => ClientMessage::Resume { token },
+            None => hello(&profile),
+        };
+        if let Some(connection) = client.get_connection_mut() {
+            connection.send(handshake);
+        }
     }
 
     for ev in connection_failed_events.read() {
         info!("Connection failed: {:?}", ev.err);
+        message.0 = format!("Connection failed: {:?}", ev.err);
+        session.resuming = false;
+        next_state.set(ClientState::MainMenu);
     }
 
     for _ in connection_lost_events.read() {
         info!("Connection lost");
+        lose_connection(&mut session, &mut message, &mut next_state, &mut evw);
     }
 }
@@ -0,0 +1,135 @@
+use crate::LocalPlayer;
+use crate::net::ReceivedMessage;
+use bevy::prelude::*;
+use bevy_quinnet::client::QuinnetClient;
+use server::net::is_listening;
+use shared::projectiles::{ExplosionEvent, Projectile};
+use shared::protocol::ServerMessage;
+use shared::session::Actor;
+
+/// Shows projectiles, matches the ones we launched with the server's, and sets them off when told.
+pub struct ProjectilePlugin;
+
+impl Plugin for ProjectilePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                replicate_projectiles,
+                expire_projectiles.run_if(not(is_listening)),
+                add_projectile_meshes,
+            ),
+        );
+    }
+}
+
+/// Seconds a projectile may outlive its fuse while waiting for the server to set it off,
+/// before it is taken for a misprediction.
+const DETONATION_GRACE: f32 = 1.0;
+
+/// Seconds on top of a round trip that one of our projectiles waits for the server to launch it
+/// too, enough for the command to wait in the server's buffer.
+const LAUNCH_GRACE: f32 = 0.25;
+
+fn replicate_projectiles(
+    mut commands: Commands,
+    mut evr: EventReader<ReceivedMessage>,
+    mut evw_explosion: EventWriter<ExplosionEvent>,
+    player: Single<&LocalPlayer>,
+    q_actors: Query<&Actor>,
+    q_projectiles: Query<(Entity, &Projectile, Option<&Actor>)>,
+) {
+    let own_actor = q_actors.get(player.pawn).ok().map(Actor::id);
+    // adopted ones only get their actor once commands are applied
+    let mut adopted = Vec::new();
+
+    for ReceivedMessage(message) in evr.read() {
+        match message {
+            ServerMessage::Launched {
+                actor,
+                owner,
+                stats,
+                translation,
+                velocity,
+            } => {
+                // ours were launched when we fired, they only lack the server's id
+                let predicted = q_projectiles
+                    .iter()
+                    .filter(|(entity, projectile, actor)| {
+                        actor.is_none()
+                            && projectile.owner == Some(player.pawn)
+                            && !adopted.contains(entity)
+                    })
+                    .max_by(|(_, a, _), (_, b, _)| a.age.total_cmp(&b.age))
+                    .map(|(entity, ..)| entity);
+
+                match predicted {
+                    Some(entity) if owner.is_some() && *owner == own_actor => {
+                        adopted.push(entity);
+                        commands.entity(entity).insert(Actor::new(*actor));
+                    }
+                    _ => {
+                        commands.spawn((
+                            Projectile::launch(None, stats.clone(), *translation, *velocity),
+                            Actor::new(*actor),
+                        ));
+                    }
+                }
+            }
+            ServerMessage::Detonated {
+                actor,
+                position,
+                explosion,
+            } => {
+                if let Some((entity, ..)) = q_projectiles
+                    .iter()
+                    .find(|(_, _, a)| a.is_some_and(|a| a.id() == *actor))
+                {
+                    commands.entity(entity).despawn();
+                }
+                evw_explosion.write(ExplosionEvent {
+                    position: *position,
+                    explosion: *explosion,
+                    source: None,
+                });
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Removes projectiles the server never set off, and ones we launched but it did not.
+fn expire_projectiles(
+    mut commands: Commands,
+    client: Res<QuinnetClient>,
+    player: Single<&LocalPlayer>,
+    q_projectiles: Query<(Entity, &Projectile, Has<Actor>)>,
+) {
+    // without a round trip to go by, ours wait as long as for a detonation
+    let rtt = client
+        .get_connection()
+        .and_then(|connection| connection.connection_stats())
+        .map_or(DETONATION_GRACE, |stats| stats.path.rtt.as_secs_f32());
+    for (entity, projectile, confirmed) in q_projectiles.iter() {
+        let unconfirmed = !confirmed && projectile.owner == Some(player.pawn);
+        if (unconfirmed && projectile.age > rtt + LAUNCH_GRACE)
+            || projectile.age > projectile.stats.fuse + DETONATION_GRACE
+        {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+fn add_projectile_meshes(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    q_projectiles: Query<(Entity, &Projectile), Added<Projectile>>,
+) {
+    for (entity, projectile) in q_projectiles.iter() {
+        commands.entity(entity).insert((
+            Mesh3d(meshes.add(Sphere::new(projectile.stats.radius))),
+            MeshMaterial3d(materials.add(Color::srgb(1.0, 0.5, 0.1))),
+        ));
+    }
+}
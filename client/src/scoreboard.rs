@@ -0,0 +1,143 @@
+use crate::ClientState;
+use crate::input::{Action, ActionState};
+use crate::net::ReceivedMessage;
+use bevy::prelude::*;
+use shared::game::{FlagState, GameMode, RoundState, Scoreboard};
+use shared::protocol::ServerMessage;
+use std::fmt::Write;
+
+/// Shows the round and its scores, as last sent by the server.
+pub struct ScoreboardPlugin;
+
+impl Plugin for ScoreboardPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Scoreboard>()
+            .add_systems(Startup, spawn_scoreboard)
+            .add_systems(
+                Update,
+                (receive_scoreboard, update_status, update_scoreboard).chain(),
+            );
+    }
+}
+
+/// One line about the round at the top of the screen.
+#[derive(Component)]
+struct StatusText;
+
+/// Table of every player, shown on demand and once the round ended.
+#[derive(Component)]
+struct ScoreboardText;
+
+fn spawn_scoreboard(mut commands: Commands) {
+    commands.spawn((
+        StatusText,
+        Text::default(),
+        TextFont {
+            font_size: 24.0,
+            ..Default::default()
+        },
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(16.0),
+            left: Val::Px(16.0),
+            ..Default::default()
+        },
+        Visibility::Hidden,
+    ));
+    commands.spawn((
+        ScoreboardText,
+        Text::default(),
+        TextFont {
+            font_size: 24.0,
+            ..Default::default()
+        },
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Percent(20.0),
+            left: Val::Percent(30.0),
+            padding: UiRect::all(Val::Px(16.0)),
+            ..Default::default()
+        },
+        BackgroundColor(Color::BLACK.with_alpha(0.6)),
+        Visibility::Hidden,
+    ));
+}
+
+fn receive_scoreboard(mut evr: EventReader<ReceivedMessage>, mut scoreboard: ResMut<Scoreboard>) {
+    for ReceivedMessage(message) in evr.read() {
+        if let ServerMessage::Scoreboard(next) = message {
+            scoreboard.set_if_neq(next.clone());
+        }
+    }
+}
+
+fn update_status(
+    scoreboard: Res<Scoreboard>,
+    state: Res<State<ClientState>>,
+    text: Single<(&mut Text, &mut Visibility), With<StatusText>>,
+) {
+    let (mut text, mut visibility) = text.into_inner();
+    let in_game = matches!(state.get(), ClientState::InGame | ClientState::Paused);
+    visibility.set_if_neq(if in_game {
+        Visibility::Inherited
+    } else {
+        Visibility::Hidden
+    });
+    if !scoreboard.is_changed() {
+        return;
+    }
+
+    let mut shown = format!("{} {}", scoreboard.mode, scoreboard.state);
+    if let Some(time_left) = scoreboard.time_left {
+        let seconds = time_left.max(0.0).ceil() as u32;
+        let _ = write!(shown, "  {}:{:02}", seconds / 60, seconds % 60);
+    }
+    for (team, score) in &scoreboard.teams {
+        let _ = write!(shown, "  {team} {score}");
+    }
+    for (team, flag) in &scoreboard.flags {
+        let _ = match flag {
+            FlagState::Home => write!(shown, "  {team} flag home"),
+            FlagState::Carried { by } => write!(shown, "  {team} flag taken by {by}"),
+            FlagState::Dropped => write!(shown, "  {team} flag dropped"),
+        };
+    }
+    text.0 = shown;
+}
+
+fn update_scoreboard(
+    scoreboard: Res<Scoreboard>,
+    actions: Res<ActionState>,
+    state: Res<State<ClientState>>,
+    text: Single<(&mut Text, &mut Visibility), With<ScoreboardText>>,
+) {
+    let (mut text, mut visibility) = text.into_inner();
+    let in_game = matches!(state.get(), ClientState::InGame | ClientState::Paused);
+    let shown =
+        in_game && (actions.pressed(Action::Scoreboard) || scoreboard.state == RoundState::Ended);
+    visibility.set_if_neq(if shown {
+        Visibility::Inherited
+    } else {
+        Visibility::Hidden
+    });
+    if !scoreboard.is_changed() {
+        return;
+    }
+
+    let mut table = format!(
+        "{:<20}{:>8}{:>8}{:>8}",
+        "player", "score", "kills", "deaths"
+    );
+    for player in &scoreboard.players {
+        let name = match (scoreboard.mode, player.team) {
+            (GameMode::Deathmatch, _) | (_, None) => player.name.clone(),
+            (_, Some(team)) => format!("[{team}] {}", player.name),
+        };
+        let _ = write!(
+            table,
+            "\n{:<20}{:>8}{:>8}{:>8}",
+            name, player.score, player.kills, player.deaths
+        );
+    }
+    text.0 = table;
+}
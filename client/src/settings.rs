@@ -0,0 +1,246 @@
+use crate::look::LookSettings;
+use bevy::pbr::DirectionalLightShadowMap;
+use bevy::prelude::*;
+use bevy::window::{PresentMode, PrimaryWindow, VideoModeSelection, WindowMode};
+use shared::console::{CommandAppExt, CommandEvent};
+use shared::maps::MapLight;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Window and graphics settings, changed through commands and applied at runtime.
+pub struct SettingsPlugin;
+
+/// Settings are stored as commands, and executed again on startup.
+const SETTINGS_PATH: &str = "settings.cfg";
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GraphicsSettings>()
+            .add_systems(Startup, load_settings)
+            .add_systems(Update, (apply_window, apply_fov, apply_shadows))
+            .add_systems(Last, (save_settings, limit_frame_rate))
+            .add_cvar("r_mode", |s: &mut GraphicsSettings| &mut s.window_mode)
+            .add_cvar("r_width", |s: &mut GraphicsSettings| &mut s.width)
+            .add_cvar("r_height", |s: &mut GraphicsSettings| &mut s.height)
+            .add_cvar("r_vsync", |s: &mut GraphicsSettings| &mut s.vsync)
+            .add_cvar("r_maxfps", |s: &mut GraphicsSettings| &mut s.max_fps)
+            .add_cvar("r_shadows", |s: &mut GraphicsSettings| &mut s.shadows)
+            .add_cvar("fov", |s: &mut GraphicsSettings| &mut s.fov);
+    }
+}
+
+#[derive(Resource, Debug, Clone)]
+pub struct GraphicsSettings {
+    pub window_mode: WindowModeSetting,
+    /// Window width, only used in [WindowModeSetting::Windowed].
+    pub width: u32,
+    /// Window height, only used in [WindowModeSetting::Windowed].
+    pub height: u32,
+    pub vsync: bool,
+    /// Upper bound of frames per second, `0` means no bound.
+    pub max_fps: u32,
+    /// Vertical field of view in degrees.
+    pub fov: f32,
+    pub shadows: ShadowQuality,
+}
+
+impl Default for GraphicsSettings {
+    fn default() -> Self {
+        Self {
+            window_mode: WindowModeSetting::Borderless,
+            width: 1280,
+            height: 720,
+            vsync: true,
+            max_fps: 0,
+            fov: crate::DEFAULT_FOV.to_degrees(),
+            shadows: ShadowQuality::Medium,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowModeSetting {
+    Windowed,
+    Borderless,
+    Fullscreen,
+}
+
+impl FromStr for WindowModeSetting {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "windowed" => Ok(Self::Windowed),
+            "borderless" => Ok(Self::Borderless),
+            "fullscreen" => Ok(Self::Fullscreen),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Display for WindowModeSetting {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Windowed => "windowed",
+            Self::Borderless => "borderless",
+            Self::Fullscreen => "fullscreen",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShadowQuality {
+    Off,
+    Low,
+    Medium,
+    High,
+}
+
+impl ShadowQuality {
+    /// Size of each shadow map cascade, if shadows are enabled at all.
+    fn map_size(&self) -> Option<usize> {
+        match self {
+            Self::Off => None,
+            Self::Low => Some(1024),
+            Self::Medium => Some(2048),
+            Self::High => Some(4096),
+        }
+    }
+}
+
+impl FromStr for ShadowQuality {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(Self::Off),
+            "low" => Ok(Self::Low),
+            "medium" => Ok(Self::Medium),
+            "high" => Ok(Self::High),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Display for ShadowQuality {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Off => "off",
+            Self::Low => "low",
+            Self::Medium => "medium",
+            Self::High => "high",
+        })
+    }
+}
+
+fn apply_window(
+    settings: Res<GraphicsSettings>,
+    mut window: Single<&mut Window, With<PrimaryWindow>>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+
+    window.mode = match settings.window_mode {
+        WindowModeSetting::Windowed => WindowMode::Windowed,
+        WindowModeSetting::Borderless => {
+            WindowMode::BorderlessFullscreen(MonitorSelection::Current)
+        }
+        WindowModeSetting::Fullscreen => {
+            WindowMode::Fullscreen(MonitorSelection::Current, VideoModeSelection::Current)
+        }
+    };
+    if settings.window_mode == WindowModeSetting::Windowed {
+        window
+            .resolution
+            .set(settings.width as f32, settings.height as f32);
+    }
+    window.present_mode = if settings.vsync {
+        PresentMode::AutoVsync
+    } else {
+        PresentMode::AutoNoVsync
+    };
+}
+
+fn apply_fov(
+    settings: Res<GraphicsSettings>,
+    mut look: ResMut<LookSettings>,
+    mut q_projection: Query<&mut Projection, With<Camera3d>>,
+) {
+    let fov = settings.fov.to_radians();
+    if settings.is_changed() {
+        // sensitivity scaling is relative to the configured field of view, not a zoomed one
+        look.reference_fov = fov;
+    }
+
+    for mut projection in q_projection.iter_mut() {
+        if !settings.is_changed() && !projection.is_added() {
+            continue;
+        }
+        if let Projection::Perspective(perspective) = projection.as_mut() {
+            perspective.fov = fov;
+        }
+    }
+}
+
+fn apply_shadows(
+    settings: Res<GraphicsSettings>,
+    mut shadow_map: ResMut<DirectionalLightShadowMap>,
+    mut q_light: Query<(&mut DirectionalLight, &MapLight)>,
+) {
+    let size = settings.shadows.map_size();
+    if settings.is_changed()
+        && let Some(size) = size
+    {
+        shadow_map.size = size;
+    }
+
+    for (mut light, map_light) in q_light.iter_mut() {
+        if settings.is_changed() || light.is_added() {
+            // lights only cast shadows if the map has them do so
+            let shadows = matches!(map_light, MapLight::Directional { shadows: true, .. });
+            light.shadows_enabled = shadows && size.is_some();
+        }
+    }
+}
+
+/// Sleeps away the rest of the frame if it finished faster than allowed.
+fn limit_frame_rate(settings: Res<GraphicsSettings>, mut last_frame: Local<Option<Instant>>) {
+    if settings.max_fps > 0 {
+        let frame_time = Duration::from_secs_f64(1.0 / settings.max_fps as f64);
+        if let Some(elapsed) = last_frame.map(|last| last.elapsed()) {
+            thread::sleep(frame_time.saturating_sub(elapsed));
+        }
+    }
+    *last_frame = Some(Instant::now());
+}
+
+fn load_settings(mut evw: EventWriter<CommandEvent>) {
+    if fs::exists(SETTINGS_PATH).unwrap_or(false) {
+        evw.write(CommandEvent::new("exec", vec![SETTINGS_PATH]));
+    }
+}
+
+fn save_settings(settings: Res<GraphicsSettings>) {
+    if !settings.is_changed() || settings.is_added() {
+        return;
+    }
+
+    let config = format!(
+        "r_mode {}\nr_width {}\nr_height {}\nr_vsync {}\nr_maxfps {}\nr_shadows {}\nfov {}\n",
+        settings.window_mode,
+        settings.width,
+        settings.height,
+        settings.vsync,
+        settings.max_fps,
+        settings.shadows,
+        settings.fov,
+    );
+
+    if let Err(err) = fs::write(SETTINGS_PATH, config) {
+        warn!("Could not save settings: {err}");
+    }
+}
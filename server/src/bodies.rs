@@ -0,0 +1,93 @@
+use crate::net::is_listening;
+use bevy::prelude::*;
+use bevy_quinnet::server::QuinnetServer;
+use bevy_rapier3d::prelude::*;
+use shared::channels::EndpointExt;
+use shared::maps::MapNode;
+use shared::protocol::{BodyState, ServerMessage};
+
+/// Sends clients the state of the map's dynamic bodies, which only the server has the final say on.
+pub struct BodyPlugin;
+
+impl Plugin for BodyPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            FixedPostUpdate,
+            replicate_bodies
+                .after(PhysicsSet::Writeback)
+                .run_if(is_listening),
+        );
+    }
+}
+
+/// Seconds between snapshots of all bodies, resting ones included, since snapshots may get lost.
+const FULL_SNAPSHOT_INTERVAL: f32 = 1.0;
+/// Bodies per message, so every message of a snapshot fits into a single datagram.
+const BODIES_PER_MESSAGE: usize = 16;
+
+fn replicate_bodies(
+    mut server: ResMut<QuinnetServer>,
+    time: Res<Time>,
+    mut sequence: Local<u32>,
+    mut since_full: Local<f32>,
+    q_bodies: Query<(&MapNode, &RigidBody, &Transform, &Velocity)>,
+) {
+    *since_full += time.delta_secs();
+    let full = *since_full >= FULL_SNAPSHOT_INTERVAL;
+    if full {
+        *since_full = 0.0;
+    }
+
+    let bodies = q_bodies
+        .iter()
+        // resting bodies are asleep, with no velocity at all
+        .filter(|(_, body, _, velocity)| {
+            **body == RigidBody::Dynamic && (full || **velocity != Velocity::zero())
+        })
+        .map(|(node, _, transform, velocity)| BodyState {
+            node: node.index as u32,
+            translation: transform.translation,
+            rotation: transform.rotation,
+            linvel: velocity.linvel,
+            angvel: velocity.angvel,
+        })
+        .collect::<Vec<_>>();
+    if bodies.is_empty() {
+        return;
+    }
+
+    *sequence = sequence.wrapping_add(1);
+    // the messages of a snapshot share its sequence, clients take each of them on its own
+    let endpoint = server.endpoint_mut();
+    for chunk in bodies.chunks(BODIES_PER_MESSAGE) {
+        endpoint.broadcast(ServerMessage::Bodies {
+            sequence: *sequence,
+            bodies: chunk.to_vec(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Smallest datagram QUIC must be able to send, less room for its headers.
+    const MAX_PAYLOAD: usize = 1200 - 100;
+
+    #[test]
+    fn full_messages_fit_into_a_datagram() {
+        let body = BodyState {
+            node: u32::MAX,
+            translation: Vec3::MAX,
+            rotation: Quat::IDENTITY,
+            linvel: Vec3::MAX,
+            angvel: Vec3::MAX,
+        };
+        let message = ServerMessage::Bodies {
+            sequence: u32::MAX,
+            bodies: vec![body; BODIES_PER_MESSAGE],
+        };
+        let bytes = bincode::serde::encode_to_vec(&message, bincode::config::standard()).unwrap();
+        assert!(bytes.len() <= MAX_PAYLOAD, "{} bytes", bytes.len());
+    }
+}
@@ -0,0 +1,455 @@
+use crate::net::is_listening;
+use bevy::prelude::*;
+use bevy_quinnet::server::QuinnetServer;
+use bevy_rapier3d::prelude::*;
+use shared::channels::EndpointExt;
+use shared::console::CommandAppExt;
+use shared::health::{DamageEvent, Dead, DeathEvent, Health, SpawnPoint};
+use shared::pawns::Possessed;
+use shared::projectiles::{ExplosionEvent, Impact, Projectile, launch_projectiles};
+use shared::protocol::ServerMessage;
+use shared::session::{Actor, Session};
+use shared::weapons::{Delivery, Loadout, LoadoutState, ShotEvent, simulate_weapons};
+use std::collections::{HashMap, HashSet};
+
+/// Hits of shots, detonations of projectiles, damage, death and respawning. Only the server decides these, and tells its clients.
+pub struct CombatPlugin;
+
+impl Plugin for CombatPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CombatSettings>()
+            .add_cvar("sv_respawn_delay", |s: &mut CombatSettings| {
+                &mut s.respawn_delay
+            })
+            .add_systems(
+                FixedUpdate,
+                (
+                    register_projectiles,
+                    detonate_projectiles,
+                    resolve_shots,
+                    explosion_damage,
+                    apply_damage,
+                    respawn,
+                )
+                    .chain()
+                    .after(simulate_weapons)
+                    .after(launch_projectiles)
+                    .run_if(is_listening),
+            )
+            .add_systems(FixedPostUpdate, replicate_loadouts.run_if(is_listening))
+            .add_systems(PostUpdate, replicate_health.run_if(is_listening));
+    }
+}
+
+/// Seconds between updates of all loadouts, so clients whose predictions went wrong while the
+/// server's state stayed the same catch up.
+const LOADOUT_SYNC_INTERVAL: f32 = 1.0;
+
+#[derive(Resource, Debug, Clone)]
+pub struct CombatSettings {
+    /// Seconds dead actors wait before they respawn.
+    pub respawn_delay: f32,
+}
+
+impl Default for CombatSettings {
+    fn default() -> Self {
+        Self { respawn_delay: 3.0 }
+    }
+}
+
+/// Counts down until a [Dead] actor respawns.
+#[derive(Component, Debug)]
+pub struct RespawnTimer(pub Timer);
+
+/// Finds what hitscan shots hit, and damages it.
+fn resolve_shots(
+    rapier: ReadRapierContext,
+    mut evr_shot: EventReader<ShotEvent>,
+    q_loadout: Query<&Loadout>,
+    q_health: Query<(), With<Health>>,
+    mut evw_damage: EventWriter<DamageEvent>,
+) -> Result {
+    let context = rapier.single()?;
+    for shot in evr_shot.read() {
+        let Some(stats) = q_loadout
+            .get(shot.shooter)
+            .ok()
+            .and_then(|loadout| loadout.weapons.get(shot.weapon))
+            .map(|weapon| &weapon.stats)
+        else {
+            continue;
+        };
+        // only hitscan shots hit instantly
+        let Delivery::Hitscan { range } = stats.delivery else {
+            continue;
+        };
+
+        let filter = QueryFilter::default().exclude_collider(shot.shooter);
+        let Some((target, distance)) =
+            context.cast_ray(shot.origin, *shot.direction, range, true, filter)
+        else {
+            continue;
+        };
+        if q_health.contains(target) {
+            evw_damage.write(DamageEvent {
+                target,
+                amount: stats.damage * stats.falloff.factor(distance),
+                source: Some(shot.shooter),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Gives new projectiles an actor id, and tells clients about them.
+fn register_projectiles(
+    mut commands: Commands,
+    mut server: ResMut<QuinnetServer>,
+    mut session: ResMut<Session>,
+    q_projectiles: Query<(Entity, &Projectile, &Transform, &Velocity), Added<Projectile>>,
+    q_actors: Query<&Actor>,
+) {
+    for (entity, projectile, transform, velocity) in q_projectiles.iter() {
+        let actor = session.actor();
+        server.endpoint_mut().broadcast(ServerMessage::Launched {
+            actor: actor.id(),
+            owner: projectile
+                .owner
+                .and_then(|owner| q_actors.get(owner).ok())
+                .map(Actor::id),
+            stats: projectile.stats.clone(),
+            translation: transform.translation,
+            velocity: velocity.linvel,
+        });
+        commands.entity(entity).insert(actor);
+    }
+}
+
+/// Sets off projectiles which hit something they go off on, or whose fuse ran out.
+fn detonate_projectiles(
+    mut commands: Commands,
+    mut server: ResMut<QuinnetServer>,
+    mut evr_collision: EventReader<CollisionEvent>,
+    mut evw_explosion: EventWriter<ExplosionEvent>,
+    q_projectiles: Query<(Entity, &Projectile, &Transform, Option<&Actor>)>,
+    q_health: Query<(), With<Health>>,
+) {
+    let mut hit = HashSet::new();
+    for ev in evr_collision.read() {
+        let CollisionEvent::Started(a, b, _) = *ev else {
+            continue;
+        };
+        for (entity, other) in [(a, b), (b, a)] {
+            let Ok((_, projectile, ..)) = q_projectiles.get(entity) else {
+                continue;
+            };
+            // launched from inside the shooter's reach, it must not go off in their face
+            if projectile.owner == Some(other) {
+                continue;
+            }
+            let detonates = match projectile.stats.impact {
+                Impact::Detonate => true,
+                Impact::DetonateOnActors => q_health.contains(other),
+                Impact::Bounce => false,
+            };
+            if detonates {
+                hit.insert(entity);
+            }
+        }
+    }
+
+    for (entity, projectile, transform, actor) in q_projectiles.iter() {
+        if !hit.contains(&entity) && projectile.age < projectile.stats.fuse {
+            continue;
+        }
+
+        let explosion = projectile.stats.explosion;
+        evw_explosion.write(ExplosionEvent {
+            position: transform.translation,
+            explosion,
+            source: projectile.owner,
+        });
+        if let Some(actor) = actor {
+            server.endpoint_mut().broadcast(ServerMessage::Detonated {
+                actor: actor.id(),
+                position: transform.translation,
+                explosion,
+            });
+        }
+        commands.entity(entity).despawn();
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn explosion_damage(
+    mut evr_explosion: EventReader<ExplosionEvent>,
+    mut evw_damage: EventWriter<DamageEvent>,
+    q_targets: Query<(Entity, &Transform), (With<Health>, Without<Dead>)>,
+) {
+    for ev in evr_explosion.read() {
+        for (target, transform) in q_targets.iter() {
+            let factor = ev
+                .explosion
+                .factor(transform.translation.distance(ev.position));
+            if factor > 0.0 {
+                evw_damage.write(DamageEvent {
+                    target,
+                    amount: ev.explosion.damage * factor,
+                    source: ev.source,
+                });
+            }
+        }
+    }
+}
+
+fn apply_damage(
+    mut commands: Commands,
+    settings: Res<CombatSettings>,
+    mut evr_damage: EventReader<DamageEvent>,
+    mut evw_death: EventWriter<DeathEvent>,
+    mut q_health: Query<&mut Health, Without<Dead>>,
+) {
+    for ev in evr_damage.read() {
+        let Ok(mut health) = q_health.get_mut(ev.target) else {
+            continue;
+        };
+        // several hits in one tick must only kill once
+        if health.is_dead() {
+            continue;
+        }
+
+        health.current = (health.current - ev.amount).max(0.0);
+        if health.is_dead() {
+            commands.entity(ev.target).insert((
+                Dead,
+                RespawnTimer(Timer::from_seconds(settings.respawn_delay, TimerMode::Once)),
+            ));
+            evw_death.write(DeathEvent {
+                target: ev.target,
+                source: ev.source,
+            });
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn respawn(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut q_dead: Query<
+        (
+            Entity,
+            &mut RespawnTimer,
+            &mut Health,
+            &mut Transform,
+            Option<&mut Velocity>,
+            Option<&mut Loadout>,
+        ),
+        With<Dead>,
+    >,
+    q_alive: Query<&Transform, (With<Health>, Without<Dead>)>,
+    q_spawn_points: Query<&GlobalTransform, With<SpawnPoint>>,
+) {
+    for (entity, mut timer, mut health, mut transform, velocity, loadout) in q_dead.iter_mut() {
+        if !timer.0.tick(time.delta()).finished() {
+            continue;
+        }
+
+        // without spawn points, actors come back where they fell
+        if let Some(spawn) = pick_spawn_point(
+            q_spawn_points.iter().map(GlobalTransform::translation),
+            q_alive.iter().map(|transform| transform.translation),
+        ) {
+            transform.translation = spawn;
+        }
+        if let Some(mut velocity) = velocity {
+            *velocity = Velocity::zero();
+        }
+        if let Some(mut loadout) = loadout {
+            loadout.refill();
+        }
+        health.current = health.max;
+        commands.entity(entity).remove::<(Dead, RespawnTimer)>();
+    }
+}
+
+/// Kills every possessed pawn on the spot, so all of them start over from a spawn point,
+/// e.g. when a round goes live or the map changes.
+pub fn respawn_everyone(
+    commands: &mut Commands,
+    q_pawns: &mut Query<(Entity, &mut Health), With<Possessed>>,
+) {
+    for (pawn, mut health) in q_pawns.iter_mut() {
+        health.current = 0.0;
+        commands
+            .entity(pawn)
+            .insert((Dead, RespawnTimer(Timer::default())));
+    }
+}
+
+/// The spawn point furthest away from everyone alive, so nobody respawns in front of a gun.
+fn pick_spawn_point(
+    spawn_points: impl Iterator<Item = Vec3>,
+    alive: impl Iterator<Item = Vec3> + Clone,
+) -> Option<Vec3> {
+    spawn_points
+        .map(|spawn| {
+            let distance = alive
+                .clone()
+                .map(|position| position.distance_squared(spawn))
+                .fold(f32::INFINITY, f32::min);
+            (spawn, distance)
+        })
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(spawn, _)| spawn)
+}
+
+fn replicate_health(
+    mut server: ResMut<QuinnetServer>,
+    mut removed_dead: RemovedComponents<Dead>,
+    q_changed: Query<(&Actor, &Health), Changed<Health>>,
+    q_actors: Query<(&Actor, &Transform)>,
+) {
+    let endpoint = server.endpoint_mut();
+    for (actor, health) in q_changed.iter() {
+        endpoint.broadcast(ServerMessage::Health {
+            actor: actor.id(),
+            health: health.current,
+        });
+    }
+    for entity in removed_dead.read() {
+        if let Ok((actor, transform)) = q_actors.get(entity) {
+            endpoint.broadcast(ServerMessage::Respawn {
+                actor: actor.id(),
+                translation: transform.translation,
+            });
+        }
+    }
+}
+
+/// Sends the state of every loadout that changed in this tick, and all of them now and then.
+fn replicate_loadouts(
+    mut server: ResMut<QuinnetServer>,
+    time: Res<Time>,
+    mut since_sync: Local<f32>,
+    mut sent: Local<HashMap<Entity, LoadoutState>>,
+    q_loadouts: Query<(Entity, &Actor, &Loadout)>,
+) {
+    *since_sync += time.delta_secs();
+    let sync = *since_sync >= LOADOUT_SYNC_INTERVAL;
+    if sync {
+        *since_sync = 0.0;
+    }
+
+    let endpoint = server.endpoint_mut();
+    let mut states = HashMap::new();
+    for (entity, actor, loadout) in q_loadouts.iter() {
+        let state = loadout.state();
+        if sync || sent.get(&entity) != Some(&state) {
+            endpoint.broadcast(ServerMessage::Loadout {
+                actor: actor.id(),
+                state: state.clone(),
+            });
+        }
+        states.insert(entity, state);
+    }
+    // despawned actors drop out
+    *sent = states;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shared::projectiles::Explosion;
+    use std::time::Duration;
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_event::<DamageEvent>()
+            .add_event::<DeathEvent>()
+            .add_event::<ExplosionEvent>()
+            .init_resource::<CombatSettings>()
+            .init_resource::<Time>()
+            .add_systems(Update, (explosion_damage, apply_damage, respawn).chain());
+        app
+    }
+
+    fn damage(app: &mut App, target: Entity, amount: f32) {
+        app.world_mut().send_event(DamageEvent {
+            target,
+            amount,
+            source: None,
+        });
+    }
+
+    #[test]
+    fn dies_once_and_respawns_at_spawn_point() {
+        let mut app = app();
+        let spawn = Vec3::new(3.0, 1.0, 0.0);
+        app.world_mut()
+            .spawn((SpawnPoint, GlobalTransform::from_translation(spawn)));
+        let actor = app
+            .world_mut()
+            .spawn((Health::default(), Transform::default()))
+            .id();
+
+        damage(&mut app, actor, 60.0);
+        damage(&mut app, actor, 60.0);
+        damage(&mut app, actor, 60.0);
+        app.update();
+        assert!(app.world().get::<Dead>(actor).is_some());
+        assert_eq!(app.world().get::<Health>(actor).unwrap().current, 0.0);
+        assert_eq!(app.world().resource::<Events<DeathEvent>>().len(), 1);
+
+        let delay = app.world().resource::<CombatSettings>().respawn_delay;
+        app.world_mut()
+            .resource_mut::<Time>()
+            .advance_by(Duration::from_secs_f32(delay));
+        app.update();
+        let world = app.world();
+        assert!(world.get::<Dead>(actor).is_none());
+        assert_eq!(world.get::<Health>(actor).unwrap(), &Health::default());
+        assert_eq!(world.get::<Transform>(actor).unwrap().translation, spawn);
+    }
+
+    #[test]
+    fn spawns_away_from_the_living() {
+        let spawn_points = [Vec3::ZERO, Vec3::X * 10.0, Vec3::X * 20.0];
+        let alive = [Vec3::X * 19.0, Vec3::X];
+        assert_eq!(
+            pick_spawn_point(spawn_points.into_iter(), alive.into_iter()),
+            Some(Vec3::X * 10.0)
+        );
+        assert_eq!(
+            pick_spawn_point([Vec3::Y].into_iter(), [].into_iter()),
+            Some(Vec3::Y)
+        );
+        assert_eq!(pick_spawn_point([].into_iter(), alive.into_iter()), None);
+    }
+
+    #[test]
+    fn explosions_hurt_less_further_out() {
+        let mut app = app();
+        let [near, far, outside] = [1.0, 3.0, 5.0].map(|x| {
+            app.world_mut()
+                .spawn((Health::default(), Transform::from_xyz(x, 0.0, 0.0)))
+                .id()
+        });
+
+        app.world_mut().send_event(ExplosionEvent {
+            position: Vec3::ZERO,
+            explosion: Explosion {
+                radius: 4.0,
+                damage: 100.0,
+                impulse: 0.0,
+            },
+            source: None,
+        });
+        app.update();
+
+        let health = |entity| app.world().get::<Health>(entity).unwrap().current;
+        assert_eq!(health(near), 25.0);
+        assert_eq!(health(far), 75.0);
+        assert_eq!(health(outside), 100.0);
+    }
+}
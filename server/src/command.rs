@@ -0,0 +1,83 @@
+use bevy::prelude::*;
+use shared::Command;
+use shared::pawns::{Controller, PawnControlSystems};
+use std::collections::VecDeque;
+
+/// Feeds buffered client commands into their controllers, one per tick.
+pub struct CommandPlugin;
+
+impl Plugin for CommandPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(FixedPreUpdate, consume_commands.before(PawnControlSystems));
+    }
+}
+
+/// Commands which arrived faster than they are simulated are dropped beyond this.
+const MAX_BUFFERED_COMMANDS: usize = 8;
+
+/// Commands of a player waiting to be simulated, whether received over the network or
+/// submitted by the local player of a listen server.
+#[derive(Component, Debug, Default)]
+#[require(Controller)]
+pub struct CommandBuffer(VecDeque<Command>);
+
+impl CommandBuffer {
+    pub fn push(&mut self, command: Command) {
+        if self.0.len() >= MAX_BUFFERED_COMMANDS {
+            self.0.pop_front();
+        }
+        self.0.push_back(command);
+    }
+}
+
+pub fn consume_commands(mut q_buffer: Query<(&mut CommandBuffer, &mut Controller)>) {
+    for (mut buffer, mut controller) in q_buffer.iter_mut() {
+        match buffer.0.pop_front() {
+            Some(command) => controller.0 = command,
+            None => {
+                // a late command keeps buttons held, but must not repeat presses
+                controller.jump = false;
+                controller.fire = false;
+                controller.reload = false;
+                controller.interact = false;
+                controller.weapon = None;
+                controller.events.clear();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+
+    #[test]
+    fn late_commands_hold_buttons_but_not_presses() {
+        let mut world = World::new();
+        let mut buffer = CommandBuffer::default();
+        buffer.push(Command {
+            forward: true,
+            jump: true,
+            fire: true,
+            reload: true,
+            interact: true,
+            weapon: Some(1),
+            ..Default::default()
+        });
+        let player = world.spawn(buffer).id();
+
+        world.run_system_once(consume_commands).unwrap();
+        assert!(world.get::<Controller>(player).unwrap().interact);
+
+        // the next command is late
+        world.run_system_once(consume_commands).unwrap();
+        let controller = world.get::<Controller>(player).unwrap();
+        assert!(controller.forward);
+        assert!(!controller.jump);
+        assert!(!controller.fire);
+        assert!(!controller.reload);
+        assert!(!controller.interact);
+        assert_eq!(controller.weapon, None);
+    }
+}
@@ -0,0 +1,36 @@
+use bevy::prelude::*;
+use shared::console::CommandEvent;
+use std::io;
+use std::sync::Mutex;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+/// Runs lines typed into the terminal as commands, so a dedicated server can be changed while running.
+pub struct StdinConsolePlugin;
+
+impl Plugin for StdinConsolePlugin {
+    fn build(&self, app: &mut App) {
+        let (tx, rx) = mpsc::channel();
+        // reading blocks, so it gets a thread of its own
+        thread::spawn(move || {
+            for line in io::stdin().lines().map_while(Result::ok) {
+                if tx.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        app.insert_resource(StdinLines(Mutex::new(rx)))
+            .add_systems(PreUpdate, read_stdin);
+    }
+}
+
+#[derive(Resource)]
+struct StdinLines(Mutex<Receiver<String>>);
+
+fn read_stdin(lines: Res<StdinLines>, mut evw: EventWriter<CommandEvent>) {
+    let Ok(rx) = lines.0.lock() else {
+        return;
+    };
+    evw.write_batch(rx.try_iter().filter_map(|line| CommandEvent::parse(&line)));
+}
@@ -0,0 +1,561 @@
+use crate::combat::respawn_everyone;
+use crate::net::is_listening;
+use bevy::prelude::*;
+use bevy::time::common_conditions::on_timer;
+use bevy_quinnet::server::QuinnetServer;
+use shared::channels::EndpointExt;
+use shared::console::{Args, CommandAppExt};
+use shared::game::{
+    FlagPlacement, FlagPlacements, FlagStand, FlagState, GameMode, PlayerScore, RoundState,
+    Scoreboard,
+};
+use shared::health::{Dead, DeathEvent, Health};
+use shared::pawns::Possessed;
+use shared::protocol::ServerMessage;
+use shared::session::{Actor, Team};
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Rules of the game mode, rounds and scores.
+pub struct GamePlugin;
+
+impl Plugin for GamePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GameSettings>()
+            .init_resource::<Game>()
+            .init_resource::<Scoreboard>()
+            .init_resource::<FlagPlacements>()
+            .add_cvar("sv_gamemode", |s: &mut GameSettings| &mut s.mode)
+            .add_cvar("sv_timelimit", |s: &mut GameSettings| &mut s.time_limit)
+            .add_cvar("sv_fraglimit", |s: &mut GameSettings| &mut s.frag_limit)
+            .add_cvar("sv_capturelimit", |s: &mut GameSettings| {
+                &mut s.capture_limit
+            })
+            .add_cvar("sv_warmup", |s: &mut GameSettings| &mut s.warmup)
+            .add_command(
+                "restart",
+                |_args: Args, mut game: ResMut<Game>, settings: Res<GameSettings>| {
+                    game.enter(RoundState::Warmup, settings.warmup);
+                },
+            )
+            .add_systems(
+                FixedUpdate,
+                (assign_teams, count_kills, update_flags, advance_round)
+                    .chain()
+                    .run_if(is_listening),
+            )
+            .add_systems(FixedPostUpdate, replicate_flags.run_if(is_listening))
+            .add_systems(
+                PostUpdate,
+                publish_scoreboard
+                    .run_if(is_listening)
+                    .run_if(on_timer(SCOREBOARD_INTERVAL)),
+            );
+    }
+}
+
+/// How often clients are sent the scoreboard.
+const SCOREBOARD_INTERVAL: Duration = Duration::from_millis(500);
+/// Seconds the result of a round is shown before the next warmup.
+const INTERMISSION: f32 = 10.0;
+/// Flags are picked up, returned and captured within this distance.
+const FLAG_REACH: f32 = 1.5;
+/// Seconds a dropped flag lies around before it returns home on its own.
+const FLAG_RETURN_TIME: f32 = 30.0;
+/// Points of a player for capturing a flag, on top of the team's.
+const CAPTURE_POINTS: i32 = 5;
+
+#[derive(Resource, Debug, Clone)]
+pub struct GameSettings {
+    /// Mode of the next round.
+    pub mode: GameMode,
+    /// Seconds a round lasts at most, `0` for no limit.
+    pub time_limit: f32,
+    /// Score of a player in deathmatch, or of a team in team deathmatch, which wins the round.
+    /// `0` for no limit.
+    pub frag_limit: u32,
+    /// Captures which win a round of capture the flag, `0` for no limit.
+    pub capture_limit: u32,
+    /// Seconds of warmup before a round.
+    pub warmup: f32,
+}
+
+impl Default for GameSettings {
+    fn default() -> Self {
+        Self {
+            mode: GameMode::Deathmatch,
+            time_limit: 600.0,
+            frag_limit: 30,
+            capture_limit: 3,
+            warmup: 20.0,
+        }
+    }
+}
+
+/// State of the current round.
+#[derive(Resource, Debug)]
+pub struct Game {
+    pub mode: GameMode,
+    pub state: RoundState,
+    /// Seconds until the state changes on its own, if it does.
+    pub time_left: Option<f32>,
+    pub team_scores: HashMap<Team, i32>,
+}
+
+impl Game {
+    fn enter(&mut self, state: RoundState, duration: f32) {
+        self.state = state;
+        self.time_left = (duration > 0.0).then_some(duration);
+    }
+}
+
+impl FromWorld for Game {
+    fn from_world(world: &mut World) -> Self {
+        let settings = world.get_resource_or_init::<GameSettings>();
+        let mut game = Self {
+            mode: settings.mode,
+            state: RoundState::Warmup,
+            time_left: None,
+            team_scores: HashMap::new(),
+        };
+        game.enter(RoundState::Warmup, settings.warmup);
+        game
+    }
+}
+
+/// Placed on players, the controllers of pawns.
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct Score {
+    pub kills: u32,
+    pub deaths: u32,
+    pub score: i32,
+}
+
+/// A flag of capture the flag, spawned at its [FlagStand] when a round goes live.
+#[derive(Component, Debug)]
+#[require(Transform)]
+pub struct Flag {
+    pub team: Team,
+    pub home: Vec3,
+    /// Pawn carrying the flag.
+    pub carrier: Option<Entity>,
+    /// Seconds until a dropped flag returns home.
+    pub dropped: Option<f32>,
+}
+
+impl Flag {
+    fn is_home(&self) -> bool {
+        self.carrier.is_none() && self.dropped.is_none()
+    }
+
+    fn return_home(&mut self, transform: &mut Transform) {
+        self.carrier = None;
+        self.dropped = None;
+        transform.translation = self.home;
+    }
+}
+
+/// Puts players without a team into the smaller one, in modes with teams.
+fn assign_teams(
+    mut commands: Commands,
+    game: Res<Game>,
+    q_players: Query<Entity, (With<Score>, Without<Team>)>,
+    q_teams: Query<&Team, With<Score>>,
+) {
+    if !game.mode.has_teams() {
+        return;
+    }
+
+    let mut counts = Team::ALL.map(|team| q_teams.iter().filter(|&&t| t == team).count());
+    for entity in q_players.iter() {
+        let index = if counts[0] <= counts[1] { 0 } else { 1 };
+        counts[index] += 1;
+        commands.entity(entity).insert(Team::ALL[index]);
+    }
+}
+
+fn count_kills(
+    mut evr_death: EventReader<DeathEvent>,
+    mut game: ResMut<Game>,
+    q_possessed: Query<&Possessed>,
+    mut q_players: Query<(&mut Score, Option<&Team>)>,
+) {
+    for ev in evr_death.read() {
+        if game.state != RoundState::Live {
+            continue;
+        }
+
+        let player = |pawn: Entity| q_possessed.get(pawn).ok().map(Possessed::controller);
+        let victim = player(ev.target);
+        let killer = ev.source.and_then(player);
+
+        let mut victim_team = None;
+        if let Some(Ok((mut score, team))) = victim.map(|victim| q_players.get_mut(victim)) {
+            score.deaths += 1;
+            victim_team = team.copied();
+        }
+
+        let Some(Ok((mut score, team))) = killer.map(|killer| q_players.get_mut(killer)) else {
+            continue;
+        };
+        let friendly = game.mode.has_teams() && team.is_some() && team.copied() == victim_team;
+        if killer == victim || friendly {
+            score.score -= 1;
+            continue;
+        }
+
+        score.kills += 1;
+        score.score += 1;
+        if game.mode == GameMode::TeamDeathmatch
+            && let Some(&team) = team
+        {
+            *game.team_scores.entry(team).or_default() += 1;
+        }
+    }
+}
+
+/// Picks up, drops, returns and captures flags.
+#[allow(clippy::type_complexity)]
+fn update_flags(
+    mut game: ResMut<Game>,
+    time: Res<Time>,
+    mut q_flags: Query<(&mut Flag, &mut Transform)>,
+    q_pawns: Query<(Entity, &Transform, &Possessed), (Without<Flag>, Without<Dead>)>,
+    mut q_players: Query<(&mut Score, Option<&Team>)>,
+) {
+    if game.state != RoundState::Live {
+        return;
+    }
+
+    let flags_home = q_flags
+        .iter()
+        .filter(|(flag, _)| flag.is_home())
+        .map(|(flag, transform)| (flag.team, transform.translation))
+        .collect::<HashMap<_, _>>();
+    let team_of = |pawn: Entity| {
+        let (_, _, possessed) = q_pawns.get(pawn).ok()?;
+        q_players.get(possessed.controller()).ok()?.1.copied()
+    };
+
+    let mut captures = Vec::new();
+    for (mut flag, mut transform) in q_flags.iter_mut() {
+        if let Some(carrier) = flag.carrier {
+            let Ok((_, carrier_transform, _)) = q_pawns.get(carrier) else {
+                // died or left
+                flag.carrier = None;
+                flag.dropped = Some(FLAG_RETURN_TIME);
+                continue;
+            };
+            transform.translation = carrier_transform.translation;
+
+            let Some(team) = team_of(carrier) else {
+                continue;
+            };
+            if flags_home
+                .get(&team)
+                .is_some_and(|home| home.distance(transform.translation) < FLAG_REACH)
+            {
+                captures.push((team, carrier));
+                flag.return_home(&mut transform);
+            }
+            continue;
+        }
+
+        if let Some(dropped) = &mut flag.dropped {
+            *dropped -= time.delta_secs();
+            if *dropped <= 0.0 {
+                flag.return_home(&mut transform);
+                continue;
+            }
+        }
+
+        for (pawn, pawn_transform, _) in q_pawns.iter() {
+            if pawn_transform.translation.distance(transform.translation) >= FLAG_REACH {
+                continue;
+            }
+            match team_of(pawn) {
+                Some(team) if team != flag.team => {
+                    flag.carrier = Some(pawn);
+                    flag.dropped = None;
+                    break;
+                }
+                Some(_) if flag.dropped.is_some() => {
+                    flag.return_home(&mut transform);
+                    break;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    for (team, carrier) in captures {
+        *game.team_scores.entry(team).or_default() += 1;
+        if let Ok((_, _, possessed)) = q_pawns.get(carrier)
+            && let Ok((mut score, _)) = q_players.get_mut(possessed.controller())
+        {
+            score.score += CAPTURE_POINTS;
+        }
+        info!("The {team} team captured a flag");
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn advance_round(
+    mut commands: Commands,
+    mut game: ResMut<Game>,
+    settings: Res<GameSettings>,
+    time: Res<Time>,
+    mut q_scores: Query<&mut Score>,
+    mut q_pawns: Query<(Entity, &mut Health), With<Possessed>>,
+    q_flags: Query<Entity, With<Flag>>,
+    q_stands: Query<(&FlagStand, &GlobalTransform)>,
+) {
+    if let Some(time_left) = &mut game.time_left {
+        *time_left -= time.delta_secs();
+    }
+    let time_up = game.time_left.is_some_and(|time_left| time_left <= 0.0);
+    // without a warmup, rounds go live right away
+    let warmed_up = time_up || game.time_left.is_none();
+
+    match game.state {
+        RoundState::Warmup if warmed_up => {
+            game.mode = settings.mode;
+            game.team_scores.clear();
+            game.enter(RoundState::Live, settings.time_limit);
+            info!("Round of {} is live", game.mode);
+
+            for mut score in q_scores.iter_mut() {
+                *score = Score::default();
+            }
+            // everyone starts the round fresh, from a spawn point
+            respawn_everyone(&mut commands, &mut q_pawns);
+            for flag in q_flags.iter() {
+                commands.entity(flag).despawn();
+            }
+            if game.mode == GameMode::CaptureTheFlag {
+                for (stand, transform) in q_stands.iter() {
+                    commands.spawn((
+                        Flag {
+                            team: stand.0,
+                            home: transform.translation(),
+                            carrier: None,
+                            dropped: None,
+                        },
+                        Transform::from_translation(transform.translation()),
+                    ));
+                }
+            }
+        }
+        RoundState::Live => {
+            let limit_reached = match game.mode {
+                GameMode::Deathmatch => {
+                    settings.frag_limit > 0
+                        && q_scores
+                            .iter()
+                            .any(|score| score.score >= settings.frag_limit as i32)
+                }
+                GameMode::TeamDeathmatch => reached(&game, settings.frag_limit),
+                GameMode::CaptureTheFlag => reached(&game, settings.capture_limit),
+            };
+            if time_up || limit_reached {
+                game.enter(RoundState::Ended, INTERMISSION);
+                info!("Round ended");
+            }
+        }
+        RoundState::Ended if time_up => {
+            game.enter(RoundState::Warmup, settings.warmup);
+        }
+        _ => {}
+    }
+}
+
+fn reached(game: &Game, limit: u32) -> bool {
+    limit > 0
+        && game
+            .team_scores
+            .values()
+            .any(|&score| score >= limit as i32)
+}
+
+fn publish_scoreboard(
+    mut server: ResMut<QuinnetServer>,
+    mut scoreboard: ResMut<Scoreboard>,
+    game: Res<Game>,
+    q_players: Query<(&Score, Option<&Name>, Option<&Team>)>,
+    q_flags: Query<&Flag>,
+    q_names: Query<&Name>,
+    q_possessed: Query<&Possessed>,
+) {
+    let mut players = q_players
+        .iter()
+        .map(|(score, name, team)| PlayerScore {
+            name: name.map_or("player", Name::as_str).to_owned(),
+            team: game.mode.has_teams().then_some(team.copied()).flatten(),
+            kills: score.kills,
+            deaths: score.deaths,
+            score: score.score,
+        })
+        .collect::<Vec<_>>();
+    players.sort_by(|a, b| b.score.cmp(&a.score).then(a.deaths.cmp(&b.deaths)));
+
+    let flags = q_flags
+        .iter()
+        .map(|flag| {
+            let state = match flag.carrier {
+                Some(carrier) => FlagState::Carried {
+                    by: q_possessed
+                        .get(carrier)
+                        .and_then(|possessed| q_names.get(possessed.controller()))
+                        .map_or("player", Name::as_str)
+                        .to_owned(),
+                },
+                None if flag.dropped.is_some() => FlagState::Dropped,
+                None => FlagState::Home,
+            };
+            (flag.team, state)
+        })
+        .collect();
+
+    let next = Scoreboard {
+        mode: game.mode,
+        state: game.state,
+        time_left: game.time_left,
+        teams: match game.mode.has_teams() {
+            true => Team::ALL
+                .map(|team| (team, game.team_scores.get(&team).copied().unwrap_or(0)))
+                .to_vec(),
+            false => Vec::new(),
+        },
+        players,
+        flags,
+    };
+    server
+        .endpoint_mut()
+        .broadcast(ServerMessage::Scoreboard(next.clone()));
+    *scoreboard = next;
+}
+
+/// Sends where the flags are whenever one moves, and now and then for clients which joined since.
+fn replicate_flags(
+    mut server: ResMut<QuinnetServer>,
+    time: Res<Time>,
+    mut since_sync: Local<f32>,
+    mut placements: ResMut<FlagPlacements>,
+    q_flags: Query<(&Flag, &Transform)>,
+    q_actors: Query<&Actor>,
+) {
+    let next = FlagPlacements(
+        q_flags
+            .iter()
+            .map(|(flag, transform)| {
+                let placement = match flag.carrier.and_then(|pawn| q_actors.get(pawn).ok()) {
+                    Some(actor) => FlagPlacement::Carried(actor.id()),
+                    None => FlagPlacement::At(transform.translation),
+                };
+                (flag.team, placement)
+            })
+            .collect(),
+    );
+
+    *since_sync += time.delta_secs();
+    // clients start out without flags, nothing to catch up on then
+    let resend = *since_sync >= SCOREBOARD_INTERVAL.as_secs_f32() && !next.0.is_empty();
+    if *placements == next && !resend {
+        return;
+    }
+    *since_sync = 0.0;
+    server
+        .endpoint_mut()
+        .broadcast(ServerMessage::Flags(next.clone()));
+    placements.set_if_neq(next);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shared::pawns::PossessExt;
+
+    fn app(mode: GameMode) -> App {
+        let mut app = App::new();
+        app.add_event::<DeathEvent>()
+            .init_resource::<Time>()
+            .insert_resource(Game {
+                mode,
+                state: RoundState::Live,
+                time_left: None,
+                team_scores: HashMap::new(),
+            })
+            .add_systems(Update, (assign_teams, count_kills).chain());
+        app
+    }
+
+    /// Spawns a player possessing a pawn, returning both.
+    fn player(app: &mut App, team: Option<Team>) -> (Entity, Entity) {
+        let world = app.world_mut();
+        let pawn = world.spawn_empty().id();
+        let mut commands = world.commands();
+        let mut player = commands.spawn(Score::default());
+        if let Some(team) = team {
+            player.insert(team);
+        }
+        let player = player.possess(pawn).id();
+        world.flush();
+        (player, pawn)
+    }
+
+    fn kill(app: &mut App, target: Entity, source: Entity) {
+        app.world_mut().send_event(DeathEvent {
+            target,
+            source: Some(source),
+        });
+        app.update();
+    }
+
+    #[test]
+    fn kills_score_for_the_killer_and_their_team() {
+        let mut app = app(GameMode::TeamDeathmatch);
+        let (red, red_pawn) = player(&mut app, Some(Team::Red));
+        let (blue, blue_pawn) = player(&mut app, Some(Team::Blue));
+        let (_, red_mate_pawn) = player(&mut app, Some(Team::Red));
+
+        kill(&mut app, blue_pawn, red_pawn);
+        let world = app.world();
+        assert_eq!(world.get::<Score>(red).unwrap().kills, 1);
+        assert_eq!(world.get::<Score>(blue).unwrap().deaths, 1);
+        assert_eq!(world.resource::<Game>().team_scores[&Team::Red], 1);
+
+        // killing a teammate or oneself costs a point
+        kill(&mut app, red_mate_pawn, red_pawn);
+        kill(&mut app, red_pawn, red_pawn);
+        let world = app.world();
+        assert_eq!(world.get::<Score>(red).unwrap().score, -1);
+        assert_eq!(world.resource::<Game>().team_scores[&Team::Red], 1);
+    }
+
+    #[test]
+    fn players_join_the_smaller_team() {
+        let mut app = app(GameMode::CaptureTheFlag);
+        player(&mut app, Some(Team::Red));
+        player(&mut app, Some(Team::Red));
+        let (first, _) = player(&mut app, None);
+        let (second, _) = player(&mut app, None);
+        let (third, _) = player(&mut app, None);
+        app.update();
+
+        let world = app.world();
+        assert_eq!(world.get::<Team>(first), Some(&Team::Blue));
+        assert_eq!(world.get::<Team>(second), Some(&Team::Blue));
+        assert_eq!(world.get::<Team>(third), Some(&Team::Red));
+    }
+
+    #[test]
+    fn nothing_counts_outside_live_rounds() {
+        let mut app = app(GameMode::Deathmatch);
+        app.world_mut().resource_mut::<Game>().state = RoundState::Warmup;
+        let (killer, killer_pawn) = player(&mut app, None);
+        let (_, victim_pawn) = player(&mut app, None);
+
+        kill(&mut app, victim_pawn, killer_pawn);
+        assert_eq!(app.world().get::<Score>(killer).unwrap().kills, 0);
+    }
+}
@@ -0,0 +1,37 @@
+use crate::bodies::BodyPlugin;
+use crate::combat::CombatPlugin;
+use crate::command::CommandPlugin;
+use crate::game::GamePlugin;
+use crate::maps::MapPlugin;
+use crate::movers::MoverPlugin;
+use crate::net::NetPlugin;
+use crate::stats::StatsPlugin;
+use bevy::prelude::*;
+
+pub mod bodies;
+pub mod combat;
+pub mod command;
+pub mod console;
+pub mod game;
+pub mod maps;
+pub mod movers;
+pub mod net;
+pub mod replay;
+pub mod stats;
+
+/// Server side game logic, run by the dedicated server and by clients hosting a listen server.
+/// Does not listen for connections until [net::start_listening] is run.
+pub struct ServerPlugin;
+
+impl Plugin for ServerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(CommandPlugin)
+            .add_plugins(NetPlugin)
+            .add_plugins(CombatPlugin)
+            .add_plugins(GamePlugin)
+            .add_plugins(MapPlugin)
+            .add_plugins(BodyPlugin)
+            .add_plugins(MoverPlugin)
+            .add_plugins(StatsPlugin);
+    }
+}
@@ -1,11 +1,12 @@
-mod net;
-mod replay;
-
-use crate::net::NetPlugin;
 use bevy::app::ScheduleRunnerPlugin;
+use bevy::gltf::GltfPlugin;
 use bevy::log::LogPlugin;
 use bevy::prelude::*;
-use shared::consts::TICK_RATE;
+use bevy::render::mesh::MeshPlugin;
+use bevy::scene::ScenePlugin;
+use server::ServerPlugin;
+use server::console::StdinConsolePlugin;
+use shared::consts::{TICK_RATE, asset_path};
 use shared::plugins::SharedPlugins;
 use std::time::Duration;
 
@@ -20,10 +21,24 @@ fn main() {
             level: bevy::log::Level::DEBUG,
             ..Default::default()
         })
+        // enough to load maps, which are rendered by clients only
+        .add_plugins((
+            AssetPlugin {
+                file_path: asset_path(),
+                ..Default::default()
+            },
+            ScenePlugin,
+            MeshPlugin,
+            GltfPlugin::default(),
+        ))
+        .init_asset::<Image>()
+        .init_asset::<StandardMaterial>()
         .add_plugins(SharedPlugins)
         // .add_plugins(ReplayPlugin {
         //     path: format!("./replays/{}.bin", Utc::now().timestamp()).into(),
         // })
-        .add_plugins(NetPlugin)
+        .add_plugins(ServerPlugin)
+        .add_plugins(StdinConsolePlugin)
+        .add_systems(Startup, server::net::start_listening)
         .run();
 }
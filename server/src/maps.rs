@@ -0,0 +1,34 @@
+use crate::combat::respawn_everyone;
+use crate::net::is_listening;
+use bevy::prelude::*;
+use bevy_quinnet::server::QuinnetServer;
+use shared::channels::EndpointExt;
+use shared::health::Health;
+use shared::maps::MapSpawnedEvent;
+use shared::pawns::Possessed;
+use shared::protocol::ServerMessage;
+
+/// Moves everyone over to a newly spawned map, and tells clients to load it.
+pub struct MapPlugin;
+
+impl Plugin for MapPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, announce_map.run_if(is_listening));
+    }
+}
+
+fn announce_map(
+    mut commands: Commands,
+    mut server: ResMut<QuinnetServer>,
+    mut evr_spawned: EventReader<MapSpawnedEvent>,
+    mut q_pawns: Query<(Entity, &mut Health), With<Possessed>>,
+) {
+    for ev in evr_spawned.read() {
+        server.endpoint_mut().broadcast(ServerMessage::Map {
+            name: ev.name.clone(),
+            hash: ev.hash,
+        });
+        // the old spawn points are gone along with the old map
+        respawn_everyone(&mut commands, &mut q_pawns);
+    }
+}
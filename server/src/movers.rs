@@ -0,0 +1,173 @@
+use crate::net::is_listening;
+use bevy::prelude::*;
+use bevy_quinnet::server::QuinnetServer;
+use bevy_rapier3d::prelude::*;
+use shared::channels::EndpointExt;
+use shared::health::Dead;
+use shared::maps::MapNode;
+use shared::movers::{Activation, Mover, MoverTrigger, move_movers};
+use shared::pawns::fps::{EYE_OFFSET, FirstPersonPawn, FirstPersonPawnCommand};
+use shared::protocol::{MoverUpdate, ServerMessage};
+use std::collections::HashSet;
+
+/// Sets movers in motion when their triggers are touched or used, and tells clients about it.
+pub struct MoverPlugin;
+
+impl Plugin for MoverPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ActivateEvent>()
+            .add_systems(
+                FixedUpdate,
+                (touch_triggers, use_triggers, activate_movers)
+                    .chain()
+                    .before(move_movers)
+                    .run_if(is_listening),
+            )
+            .add_systems(FixedPostUpdate, sync_movers.run_if(is_listening));
+    }
+}
+
+/// How far away pawns can use things.
+const USE_RANGE: f32 = 2.0;
+
+/// Seconds between updates of all movers, so clients which joined late or drifted catch up.
+const SYNC_INTERVAL: f32 = 1.0;
+
+/// Sets the movers of the given names in motion.
+#[derive(Event, Debug)]
+struct ActivateEvent {
+    targets: Vec<String>,
+}
+
+/// Activates touch triggers as soon as a pawn enters them.
+fn touch_triggers(
+    rapier: ReadRapierContext,
+    mut occupied: Local<HashSet<Entity>>,
+    q_triggers: Query<(Entity, &MoverTrigger, &Collider, &GlobalTransform)>,
+    q_pawns: Query<(), (With<FirstPersonPawn>, Without<Dead>)>,
+    mut evw_activate: EventWriter<ActivateEvent>,
+) -> Result {
+    let context = rapier.single()?;
+    for (entity, trigger, collider, transform) in q_triggers.iter() {
+        if trigger.on != Activation::Touch {
+            continue;
+        }
+        let (_, rotation, translation) = transform.to_scale_rotation_translation();
+        let predicate = |entity| q_pawns.contains(entity);
+        let filter = QueryFilter::default().predicate(&predicate);
+        let mut touched = false;
+        context.intersections_with_shape(translation, rotation, collider, filter, |_| {
+            touched = true;
+            false
+        });
+
+        if !touched {
+            occupied.remove(&entity);
+        } else if occupied.insert(entity) {
+            evw_activate.write(ActivateEvent {
+                targets: trigger.targets.clone(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Activates use triggers which pawns look at while they use.
+fn use_triggers(
+    rapier: ReadRapierContext,
+    q_pawns: Query<
+        (
+            Entity,
+            &FirstPersonPawn,
+            &FirstPersonPawnCommand,
+            &Transform,
+        ),
+        Without<Dead>,
+    >,
+    q_triggers: Query<&MoverTrigger>,
+    mut evw_activate: EventWriter<ActivateEvent>,
+) -> Result {
+    let context = rapier.single()?;
+    for (entity, pawn, command, transform) in q_pawns.iter() {
+        if !command.interact {
+            continue;
+        }
+        let filter = QueryFilter::default()
+            .exclude_collider(entity)
+            .exclude_sensors();
+        let Some((target, _)) = context.cast_ray(
+            transform.translation + EYE_OFFSET,
+            pawn.view_rotation() * Vec3::NEG_Z,
+            USE_RANGE,
+            true,
+            filter,
+        ) else {
+            continue;
+        };
+        let Ok(trigger) = q_triggers.get(target) else {
+            continue;
+        };
+        if trigger.on == Activation::Use {
+            evw_activate.write(ActivateEvent {
+                targets: trigger.targets.clone(),
+            });
+        }
+    }
+    Ok(())
+}
+
+fn activate_movers(
+    mut server: ResMut<QuinnetServer>,
+    mut evr_activate: EventReader<ActivateEvent>,
+    mut q_movers: Query<(&Name, &MapNode, &mut Mover)>,
+) {
+    let targets = evr_activate
+        .read()
+        .flat_map(|ev| ev.targets.iter().map(String::as_str))
+        .collect::<HashSet<_>>();
+    if targets.is_empty() {
+        return;
+    }
+
+    let updates = q_movers
+        .iter_mut()
+        .filter(|(name, ..)| targets.contains(name.as_str()))
+        .filter_map(|(_, node, mut mover)| {
+            mover.activate().then(|| MoverUpdate {
+                node: node.index as u32,
+                state: mover.state,
+            })
+        })
+        .collect::<Vec<_>>();
+    if !updates.is_empty() {
+        server
+            .endpoint_mut()
+            .broadcast(ServerMessage::Movers(updates));
+    }
+}
+
+fn sync_movers(
+    mut server: ResMut<QuinnetServer>,
+    time: Res<Time>,
+    mut since_sync: Local<f32>,
+    q_movers: Query<(&MapNode, &Mover)>,
+) {
+    *since_sync += time.delta_secs();
+    if *since_sync < SYNC_INTERVAL {
+        return;
+    }
+    *since_sync = 0.0;
+
+    let updates = q_movers
+        .iter()
+        .map(|(node, mover)| MoverUpdate {
+            node: node.index as u32,
+            state: mover.state,
+        })
+        .collect::<Vec<_>>();
+    if !updates.is_empty() {
+        server
+            .endpoint_mut()
+            .broadcast(ServerMessage::Movers(updates));
+    }
+}
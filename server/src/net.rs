@@ -1,57 +1,634 @@
+use crate::command::CommandBuffer;
+use crate::game::Score;
+use bevy::ecs::component::HookContext;
+use bevy::ecs::world::DeferredWorld;
 use bevy::prelude::*;
+use bevy::time::common_conditions::on_timer;
 use bevy_quinnet::server::certificate::CertificateRetrievalMode;
 use bevy_quinnet::server::{
-    ConnectionEvent, ConnectionLostEvent, QuinnetServer, QuinnetServerPlugin,
+    ConnectionEvent, ConnectionLostEvent, Endpoint, QuinnetServer, QuinnetServerPlugin,
     ServerEndpointConfiguration,
 };
-use bevy_quinnet::shared::channels::ChannelsConfiguration;
-use shared::consts::GAME_PORT;
+use bevy_quinnet::shared::ClientId;
+use shared::channels::{self, EndpointExt, NetMessage};
+use shared::conditioner::{LinkConditioner, LinkQueue};
+use shared::console::CommandAppExt;
+use shared::consts::{BUILD_HASH, GAME_PORT, HEARTBEAT_INTERVAL, PROTOCOL_VERSION};
+use shared::maps::CurrentMap;
+use shared::pawns::fps::FirstPersonPawn;
+use shared::pawns::{Controller, PossessExt, Possesses};
+use shared::protocol::{ClientMessage, Hello, ServerMessage};
+use shared::session::{Actor, Session};
+use std::collections::HashMap;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
 
 pub struct NetPlugin;
 
+/// A player connected over the network, the [Controller](shared::pawns::Controller) of their pawn.
 #[derive(Component, Deref)]
-#[component(immutable)]
-struct Client {
-    pub id: u64,
+#[component(immutable, on_insert = Client::on_insert, on_replace = Client::on_replace)]
+#[require(CommandBuffer, Incoming, LastSeen, Score)]
+pub struct Client {
+    #[deref]
+    pub id: ClientId,
+    pub name: String,
 }
 
+impl Client {
+    fn on_insert(mut world: DeferredWorld, ctx: HookContext) {
+        let id = world.get::<Client>(ctx.entity).unwrap().id;
+        world.resource_mut::<Clients>().0.insert(id, ctx.entity);
+    }
+
+    fn on_replace(mut world: DeferredWorld, ctx: HookContext) {
+        let id = world.get::<Client>(ctx.entity).unwrap().id;
+        let mut clients = world.resource_mut::<Clients>();
+        if clients.0.get(&id) == Some(&ctx.entity) {
+            clients.0.remove(&id);
+        }
+    }
+}
+
+/// Entities of all joined clients by their id, kept up to date by [Client] itself.
+#[derive(Resource, Debug, Default)]
+pub struct Clients(HashMap<ClientId, Entity>);
+
+impl Clients {
+    pub fn get(&self, id: ClientId) -> Option<Entity> {
+        self.0.get(&id).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Messages received from a client, waiting for the [LinkConditioner] to let them through.
+#[derive(Component, Default, Deref, DerefMut)]
+struct Incoming(LinkQueue<ClientMessage>);
+
+/// Real time a message of the client was last received at, in seconds.
+#[derive(Component, Default)]
+struct LastSeen(f64);
+
+/// Secret handed to a joined client, which lets it resume its session after dropping.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReconnectToken(pub u64);
+
+impl ReconnectToken {
+    fn generate() -> Self {
+        // every hasher state is randomly keyed, good enough to not be guessable
+        Self(RandomState::new().build_hasher().finish())
+    }
+}
+
+/// Placed on a client which lost its connection. It keeps its pawn until the deadline,
+/// in case it comes back with its [ReconnectToken].
+#[derive(Component, Debug)]
+pub struct Detached {
+    pub name: String,
+    /// Real time the session is dropped at, in seconds.
+    pub deadline: f64,
+}
+
+/// Connections which have not completed their handshake yet.
+#[derive(Resource, Default)]
+pub struct PendingClients {
+    /// Real time each connected at, in seconds.
+    connecting: HashMap<ClientId, f64>,
+    /// Told why they may not join, with the real time they are disconnected at.
+    rejected: HashMap<ClientId, f64>,
+}
+
+impl PendingClients {
+    /// Takes the connections to close: rejected ones once their rejection had time to arrive,
+    /// and ones which took too long to introduce themselves.
+    fn take_stale(&mut self, now: f64) -> Vec<ClientId> {
+        self.rejected
+            .extract_if(|_, &mut close_at| now >= close_at)
+            .chain(
+                self.connecting
+                    .extract_if(|_, &mut since| now - since > HANDSHAKE_TIMEOUT),
+            )
+            .map(|(id, _)| id)
+            .collect()
+    }
+}
+
+#[derive(Resource, Debug, Clone)]
+pub struct ServerSettings {
+    pub max_players: usize,
+    /// Seconds without any message after which a client is dropped.
+    pub timeout: f32,
+    /// Seconds a dropped client may take to reconnect and resume its session.
+    pub reconnect_grace: f32,
+}
+
+impl Default for ServerSettings {
+    fn default() -> Self {
+        Self {
+            max_players: 16,
+            timeout: 10.0,
+            reconnect_grace: 60.0,
+        }
+    }
+}
+
+/// Longer names are cut off.
+const MAX_NAME_LEN: usize = 32;
+
+/// Seconds a connection may take to send its handshake.
+const HANDSHAKE_TIMEOUT: f64 = 5.0;
+
+/// Seconds a rejected connection stays open, so it receives the reason before it is closed.
+const REJECT_LINGER: f64 = 1.0;
+
 impl Plugin for NetPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins(QuinnetServerPlugin::default());
-        app.add_systems(Startup, start_listening);
-        app.add_systems(Update, handle_server_events);
+        app.init_resource::<Clients>()
+            .init_resource::<PendingClients>()
+            .init_resource::<ServerSettings>()
+            .add_cvar("sv_maxplayers", |s: &mut ServerSettings| &mut s.max_players)
+            .add_cvar("sv_timeout", |s: &mut ServerSettings| &mut s.timeout)
+            .add_cvar("sv_reconnect_grace", |s: &mut ServerSettings| {
+                &mut s.reconnect_grace
+            });
+        app.add_systems(
+            PreUpdate,
+            (handshake, receive_messages).run_if(is_listening),
+        );
+        app.add_systems(
+            Update,
+            (
+                handle_server_events,
+                (
+                    check_timeouts,
+                    close_stale_connections,
+                    send_heartbeats.run_if(on_timer(HEARTBEAT_INTERVAL)),
+                )
+                    .run_if(is_listening),
+                expire_detached,
+            ),
+        );
+    }
+}
+
+/// Opens the game port, so clients can connect.
+pub fn listen(server: &mut QuinnetServer) -> Result {
+    server.start_endpoint(
+        ServerEndpointConfiguration::from_string(format!("[::]:{GAME_PORT}").as_str())?,
+        CertificateRetrievalMode::GenerateSelfSigned {
+            server_hostname: "::1".to_string(),
+        },
+        channels::configuration(),
+    )?;
+    Ok(())
+}
+
+/// Run condition for systems which only make sense while clients can connect.
+pub fn is_listening(server: Res<QuinnetServer>) -> bool {
+    server.is_listening()
+}
+
+pub fn start_listening(mut server: ResMut<QuinnetServer>) -> Result {
+    listen(&mut server)
+}
+
+/// Closes the game port, and removes all clients along with their pawns.
+#[allow(clippy::type_complexity)]
+pub fn stop_listening(
+    mut server: ResMut<QuinnetServer>,
+    mut pending: ResMut<PendingClients>,
+    mut commands: Commands,
+    clients: Query<(Entity, Option<&Possesses>), Or<(With<Client>, With<Detached>)>>,
+) {
+    if let Err(err) = server.stop_endpoint() {
+        warn!("Could not stop server: {err:?}");
+    }
+    *pending = PendingClients::default();
+    for (entity, possesses) in clients.iter() {
+        if let Some(possesses) = possesses {
+            commands.entity(possesses.0).despawn();
+        }
+        commands.entity(entity).despawn();
+    }
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn handshake(
+    mut server: ResMut<QuinnetServer>,
+    mut pending: ResMut<PendingClients>,
+    settings: Res<ServerSettings>,
+    mut session: ResMut<Session>,
+    mut commands: Commands,
+    clients: Res<Clients>,
+    q_sessions: Query<(
+        Entity,
+        &ReconnectToken,
+        &Possesses,
+        Option<&Client>,
+        Option<&Detached>,
+    )>,
+    q_detached: Query<(), With<Detached>>,
+    q_actors: Query<&Actor>,
+    map: Res<CurrentMap>,
+    time: Res<Time<Real>>,
+) {
+    let now = time.elapsed_secs_f64();
+    let endpoint = server.endpoint_mut();
+    // joining clients are only indexed once their commands are applied,
+    // and detached ones keep their place until their session expires
+    let mut players = clients.len() + q_detached.iter().count();
+
+    let PendingClients {
+        connecting,
+        rejected,
+    } = pending.as_mut();
+    connecting.retain(|&id, _| {
+        let Some(message) = endpoint.receive_from::<ClientMessage>(id) else {
+            return true;
+        };
+        let hello = match message {
+            ClientMessage::Hello(hello) => hello,
+            ClientMessage::Resume { token } => {
+                let Some((entity, name, previous, actor)) = q_sessions
+                    .iter()
+                    .filter(|(_, t, ..)| t.0 == token)
+                    .find_map(|(entity, _, possesses, client, detached)| {
+                        let name = client
+                            .map(|client| &client.name)
+                            .or(detached.map(|detached| &detached.name))?;
+                        let actor = q_actors.get(possesses.0).ok()?;
+                        Some((entity, name.clone(), client.map(|client| client.id), actor))
+                    })
+                else {
+                    // the client introduces itself from scratch next
+                    endpoint.send_to(id, ServerMessage::Expired);
+                    return true;
+                };
+
+                // the client may notice a drop before the server does, its old connection is stale then
+                if let Some(previous) = previous {
+                    endpoint.try_disconnect_client(previous);
+                }
+                info!("Client {id} resumed the session of {name}");
+                commands.entity(entity).remove::<Detached>().insert((
+                    Client { id, name },
+                    LastSeen(now),
+                    Incoming::default(),
+                ));
+                endpoint.send_to(
+                    id,
+                    ServerMessage::Welcome {
+                        token,
+                        actor: actor.id(),
+                    },
+                );
+                send_map(endpoint, id, &map);
+                return false;
+            }
+            _ => {
+                warn!("Client {id} sent a message before its handshake");
+                return true;
+            }
+        };
+
+        if let Err(reason) = check_hello(&hello, players, &settings) {
+            info!("Rejected client {id}: {reason}");
+            endpoint.send_to(id, ServerMessage::Rejected { reason });
+            rejected.insert(id, now + REJECT_LINGER);
+            return false;
+        }
+        if hello.build != BUILD_HASH {
+            warn!(
+                "Client {id} runs build {}, server runs {BUILD_HASH}",
+                hello.build
+            );
+        }
+
+        let name = player_name(&hello.name);
+        info!("Client {id} joined as {name}");
+        let actor = session.actor();
+        let actor_id = actor.id();
+        let pawn = commands.spawn((FirstPersonPawn::default(), actor)).id();
+        let token = ReconnectToken::generate();
+        let mut client = commands.spawn((
+            Name::new(name.clone()),
+            Client { id, name },
+            token,
+            LastSeen(now),
+        ));
+        client.possess(pawn);
+        if let Some(team) = hello.team {
+            client.insert(team);
+        }
+        endpoint.send_to(
+            id,
+            ServerMessage::Welcome {
+                token: token.0,
+                actor: actor_id,
+            },
+        );
+        send_map(endpoint, id, &map);
+        players += 1;
+        false
+    });
+}
+
+/// Returns why a client may not join, if it may not.
+fn check_hello(hello: &Hello, players: usize, settings: &ServerSettings) -> Result<(), String> {
+    if hello.version != PROTOCOL_VERSION {
+        return Err(format!(
+            "Server runs protocol version {PROTOCOL_VERSION}, client runs {}",
+            hello.version
+        ));
+    }
+    if players >= settings.max_players {
+        return Err(format!("Server is full ({} players)", settings.max_players));
+    }
+    Ok(())
+}
+
+fn player_name(name: &str) -> String {
+    let name = name
+        .chars()
+        .filter(|c| !c.is_control())
+        .take(MAX_NAME_LEN)
+        .collect::<String>();
+    match name.trim() {
+        "" => "player".to_owned(),
+        name => name.to_owned(),
     }
 }
 
-fn start_listening(mut server: ResMut<QuinnetServer>) {
-    server
-        .start_endpoint(
-            ServerEndpointConfiguration::from_string(format!("[::]:{GAME_PORT}").as_str()).unwrap(),
-            CertificateRetrievalMode::GenerateSelfSigned {
-                server_hostname: "::1".to_string(),
+fn receive_messages(
+    mut server: ResMut<QuinnetServer>,
+    conditioner: Res<LinkConditioner>,
+    time: Res<Time<Real>>,
+    mut clients: Query<(&Client, &mut Incoming, &mut CommandBuffer, &mut LastSeen)>,
+) {
+    let now = time.elapsed_secs_f64();
+    let endpoint = server.endpoint_mut();
+    for (client, mut incoming, mut buffer, mut last_seen) in clients.iter_mut() {
+        while let Some(message) = endpoint.receive_from::<ClientMessage>(client.id) {
+            last_seen.0 = now;
+            incoming.push(&conditioner, now, message.channel().reliable(), message);
+        }
+
+        while let Some(message) = incoming.pop(now) {
+            match message {
+                ClientMessage::Hello(_) | ClientMessage::Resume { .. } => {
+                    warn!("Client {} repeated its handshake", client.id)
+                }
+                ClientMessage::Command(command) => buffer.push(command),
+                ClientMessage::Heartbeat => {}
+            }
+        }
+    }
+}
+
+fn send_heartbeats(mut server: ResMut<QuinnetServer>) {
+    server.endpoint_mut().broadcast(ServerMessage::Heartbeat);
+}
+
+/// Drops clients which have not been heard of for too long, even if their connection is still open.
+fn check_timeouts(
+    mut server: ResMut<QuinnetServer>,
+    mut commands: Commands,
+    settings: Res<ServerSettings>,
+    time: Res<Time<Real>>,
+    clients: Query<(Entity, &Client, &LastSeen)>,
+) {
+    let now = time.elapsed_secs_f64();
+    for (entity, client, last_seen) in clients.iter() {
+        if now - last_seen.0 > settings.timeout as f64 {
+            info!("Client {} timed out", client.id);
+            server.endpoint_mut().try_disconnect_client(client.id);
+            detach(
+                &mut commands,
+                entity,
+                client,
+                now + settings.reconnect_grace as f64,
+            );
+        }
+    }
+}
+
+fn close_stale_connections(
+    mut server: ResMut<QuinnetServer>,
+    mut pending: ResMut<PendingClients>,
+    time: Res<Time<Real>>,
+) {
+    for id in pending.take_stale(time.elapsed_secs_f64()) {
+        info!("Closing connection of client {id}, which did not join");
+        server.endpoint_mut().try_disconnect_client(id);
+    }
+}
+
+/// Tells a client which map to load, unless it is still loading here too and announced later.
+fn send_map(endpoint: &mut Endpoint, id: ClientId, map: &CurrentMap) {
+    if let Some(hash) = map.hash {
+        endpoint.send_to(
+            id,
+            ServerMessage::Map {
+                name: map.name.clone(),
+                hash,
             },
-            ChannelsConfiguration::default(),
-        )
-        .unwrap();
+        );
+    }
 }
 
+/// Keeps the pawn of a client which lost its connection around for a while, standing still.
+fn detach(commands: &mut Commands, entity: Entity, client: &Client, deadline: f64) {
+    commands.entity(entity).remove::<Client>().insert((
+        Detached {
+            name: client.name.clone(),
+            deadline,
+        },
+        Controller::default(),
+        CommandBuffer::default(),
+    ));
+}
+
+fn expire_detached(
+    mut commands: Commands,
+    time: Res<Time<Real>>,
+    q_detached: Query<(Entity, &Detached, Option<&Possesses>)>,
+) {
+    let now = time.elapsed_secs_f64();
+    for (entity, detached, possesses) in q_detached.iter() {
+        if now < detached.deadline {
+            continue;
+        }
+        info!("Session of {} expired", detached.name);
+        if let Some(possesses) = possesses {
+            commands.entity(possesses.0).despawn();
+        }
+        commands.entity(entity).despawn();
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn handle_server_events(
     mut connection_events: EventReader<ConnectionEvent>,
     mut connection_lost_events: EventReader<ConnectionLostEvent>,
     mut commands: Commands,
-    clients: Query<(Entity, &Client)>,
+    mut pending: ResMut<PendingClients>,
+    clients: Res<Clients>,
+    q_clients: Query<&Client>,
+    settings: Res<ServerSettings>,
+    time: Res<Time<Real>>,
 ) {
     for &ConnectionEvent { id } in connection_events.read() {
         info!("Client {id} connected");
-        commands.spawn(Client { id });
+        pending.connecting.insert(id, time.elapsed_secs_f64());
     }
 
     for &ConnectionLostEvent { id } in connection_lost_events.read() {
         info!("Client {id} disconnected");
-        let Some((entity, _)) = clients.into_iter().find(|&(_, c)| c.id == id) else {
-            warn!("Could not find entity for client {id}");
-            return;
+        if pending.connecting.remove(&id).is_some() || pending.rejected.remove(&id).is_some() {
+            continue;
+        }
+        let Some((entity, client)) = clients
+            .get(id)
+            .and_then(|entity| Some((entity, q_clients.get(entity).ok()?)))
+        else {
+            // already dropped after timing out
+            debug!("Could not find entity for client {id}");
+            continue;
         };
-        commands.entity(entity).despawn();
+        let deadline = time.elapsed_secs_f64() + settings.reconnect_grace as f64;
+        detach(&mut commands, entity, client, deadline);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_event::<ConnectionEvent>()
+            .add_event::<ConnectionLostEvent>()
+            .init_resource::<Clients>()
+            .init_resource::<PendingClients>()
+            .init_resource::<ServerSettings>()
+            .init_resource::<Time<Real>>()
+            .add_systems(Update, (handle_server_events, expire_detached));
+        app
+    }
+
+    /// Spawns a joined client possessing a pawn, returning both.
+    fn join(app: &mut App, id: ClientId) -> (Entity, Entity) {
+        let world = app.world_mut();
+        let pawn = world.spawn_empty().id();
+        let client = world
+            .commands()
+            .spawn(Client {
+                id,
+                name: format!("client{id}"),
+            })
+            .possess(pawn)
+            .id();
+        world.flush();
+        (client, pawn)
+    }
+
+    #[test]
+    fn index_follows_client_entities() {
+        let mut app = app();
+        let (client, _) = join(&mut app, 7);
+        assert_eq!(app.world().resource::<Clients>().get(7), Some(client));
+
+        app.world_mut().despawn(client);
+        assert_eq!(app.world().resource::<Clients>().get(7), None);
+    }
+
+    #[test]
+    fn handles_all_disconnects_of_a_frame() {
+        let mut app = app();
+        let (first, first_pawn) = join(&mut app, 1);
+        let (second, second_pawn) = join(&mut app, 2);
+        let (third, _) = join(&mut app, 3);
+
+        // an unknown client must not keep the others from being removed
+        for id in [42, 1, 2] {
+            app.world_mut().send_event(ConnectionLostEvent { id });
+        }
+        app.update();
+
+        let world = app.world();
+        for entity in [first, second] {
+            assert!(world.get::<Client>(entity).is_none());
+            assert!(world.get::<Detached>(entity).is_some());
+        }
+        // pawns wait for their players to come back
+        for entity in [first_pawn, second_pawn] {
+            assert!(world.get_entity(entity).is_ok());
+        }
+        assert!(world.get::<Client>(third).is_some());
+        assert_eq!(world.resource::<Clients>().len(), 1);
+        assert_eq!(world.resource::<Clients>().get(3), Some(third));
+    }
+
+    #[test]
+    fn detached_sessions_expire_after_grace() {
+        let mut app = app();
+        app.world_mut()
+            .resource_mut::<ServerSettings>()
+            .reconnect_grace = 0.0;
+        let (client, pawn) = join(&mut app, 1);
+
+        app.world_mut().send_event(ConnectionLostEvent { id: 1 });
+        app.update();
+        assert!(app.world().get::<Detached>(client).is_some());
+
+        app.update();
+        assert!(app.world().get_entity(client).is_err());
+        assert!(app.world().get_entity(pawn).is_err());
+    }
+
+    #[test]
+    fn disconnect_before_handshake_leaves_no_trace() {
+        let mut app = app();
+        app.world_mut().send_event(ConnectionEvent { id: 5 });
+        app.update();
+        assert!(
+            app.world()
+                .resource::<PendingClients>()
+                .connecting
+                .contains_key(&5)
+        );
+
+        app.world_mut().send_event(ConnectionLostEvent { id: 5 });
+        app.update();
+        assert!(
+            app.world()
+                .resource::<PendingClients>()
+                .connecting
+                .is_empty()
+        );
+        assert!(app.world().resource::<Clients>().is_empty());
+    }
+
+    #[test]
+    fn closes_rejected_and_silent_connections() {
+        let mut pending = PendingClients::default();
+        pending.connecting.insert(1, 0.0);
+        pending.connecting.insert(2, 4.0);
+        pending.rejected.insert(3, 2.0);
+        assert!(pending.take_stale(1.0).is_empty());
+
+        let mut stale = pending.take_stale(6.0);
+        stale.sort();
+        assert_eq!(stale, [1, 3]);
+        assert!(pending.connecting.contains_key(&2));
+        assert!(pending.rejected.is_empty());
     }
 }
@@ -0,0 +1,55 @@
+use crate::net::is_listening;
+use bevy::prelude::*;
+use bevy::time::common_conditions::on_timer;
+use bevy_quinnet::server::QuinnetServer;
+use shared::channels::EndpointExt;
+use shared::protocol::ServerMessage;
+use std::time::{Duration, Instant};
+
+/// Measures how long server updates take, and regularly reports it to all clients.
+pub struct StatsPlugin;
+
+impl Plugin for StatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TickStats>()
+            .add_systems(First, begin_tick)
+            .add_systems(
+                Last,
+                (
+                    end_tick,
+                    broadcast_stats
+                        .run_if(on_timer(Duration::from_secs(1)))
+                        .run_if(is_listening),
+                )
+                    .chain(),
+            );
+    }
+}
+
+#[derive(Resource, Default)]
+struct TickStats {
+    start: Option<Instant>,
+    total: Duration,
+    count: u32,
+}
+
+fn begin_tick(mut stats: ResMut<TickStats>) {
+    stats.start = Some(Instant::now());
+}
+
+fn end_tick(mut stats: ResMut<TickStats>) {
+    if let Some(start) = stats.start.take() {
+        stats.total += start.elapsed();
+        stats.count += 1;
+    }
+}
+
+fn broadcast_stats(mut stats: ResMut<TickStats>, mut server: ResMut<QuinnetServer>) {
+    let tick_time = stats.total.as_secs_f32() / stats.count.max(1) as f32;
+    stats.total = Duration::ZERO;
+    stats.count = 0;
+
+    server
+        .endpoint_mut()
+        .broadcast(ServerMessage::Stats { tick_time });
+}
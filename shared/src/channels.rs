@@ -0,0 +1,124 @@
+use bevy_quinnet::shared::channels::{
+    ChannelId, ChannelKind, ChannelsConfiguration, DEFAULT_MAX_RELIABLE_FRAME_LEN,
+};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+/// Every channel both sides open, the ids are their position in [Channel::ALL].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Channel {
+    /// Player commands, sent every tick and quickly outdated.
+    Input,
+    /// World state, sent every tick and quickly outdated.
+    Snapshots,
+    /// Game events which must arrive in the order they happened.
+    Events,
+    /// Keepalive messages, only their arrival matters.
+    Heartbeat,
+}
+
+impl Channel {
+    pub const ALL: [Self; 4] = [Self::Input, Self::Snapshots, Self::Events, Self::Heartbeat];
+
+    pub fn id(self) -> ChannelId {
+        self as ChannelId
+    }
+
+    pub fn kind(self) -> ChannelKind {
+        match self {
+            Self::Input | Self::Snapshots | Self::Heartbeat => ChannelKind::Unreliable,
+            Self::Events => ChannelKind::OrderedReliable {
+                max_frame_size: DEFAULT_MAX_RELIABLE_FRAME_LEN,
+            },
+        }
+    }
+
+    /// Whether messages on this channel always arrive, unreliable ones may be lost, duplicated or reordered.
+    pub fn reliable(self) -> bool {
+        !matches!(self.kind(), ChannelKind::Unreliable)
+    }
+}
+
+impl From<Channel> for ChannelId {
+    fn from(channel: Channel) -> Self {
+        channel.id()
+    }
+}
+
+/// Channels to open connections and endpoints with, the same on both sides.
+pub fn configuration() -> ChannelsConfiguration {
+    ChannelsConfiguration::from_types(Channel::ALL.map(Channel::kind).to_vec())
+        .expect("channel registry should fit into a configuration")
+}
+
+/// A message type, which knows the channel each of its messages belongs on.
+pub trait NetMessage: Serialize + DeserializeOwned {
+    fn channel(&self) -> Channel;
+}
+
+#[cfg(feature = "client")]
+pub trait ClientConnectionExt {
+    /// Sends a message on its channel.
+    fn send<M: NetMessage>(&mut self, message: M);
+
+    /// Receives the next message, dropping any which arrived on a channel they don't belong on.
+    fn receive<M: NetMessage>(&mut self) -> Option<M>;
+}
+
+#[cfg(feature = "client")]
+impl ClientConnectionExt for bevy_quinnet::client::connection::ClientSideConnection {
+    fn send<M: NetMessage>(&mut self, message: M) {
+        self.try_send_message_on(message.channel(), message);
+    }
+
+    fn receive<M: NetMessage>(&mut self) -> Option<M> {
+        loop {
+            let (channel, message) = self.try_receive_message::<M>()?;
+            if channel == message.channel().id() {
+                return Some(message);
+            }
+            bevy::log::warn!("Dropped message received on unexpected channel {channel}");
+        }
+    }
+}
+
+#[cfg(feature = "server")]
+pub trait EndpointExt {
+    /// Sends a message to one client on its channel.
+    fn send_to<M: NetMessage>(&mut self, client_id: bevy_quinnet::shared::ClientId, message: M);
+
+    /// Sends a message to every client on its channel.
+    fn broadcast<M: NetMessage>(&mut self, message: M);
+
+    /// Receives the next message of a client, dropping any which arrived on a channel they don't belong on.
+    fn receive_from<M: NetMessage>(
+        &mut self,
+        client_id: bevy_quinnet::shared::ClientId,
+    ) -> Option<M>;
+}
+
+#[cfg(feature = "server")]
+impl EndpointExt for bevy_quinnet::server::Endpoint {
+    fn send_to<M: NetMessage>(&mut self, client_id: bevy_quinnet::shared::ClientId, message: M) {
+        self.try_send_message_on(client_id, message.channel(), message);
+    }
+
+    fn broadcast<M: NetMessage>(&mut self, message: M) {
+        self.try_broadcast_message_on(message.channel(), message);
+    }
+
+    fn receive_from<M: NetMessage>(
+        &mut self,
+        client_id: bevy_quinnet::shared::ClientId,
+    ) -> Option<M> {
+        loop {
+            let (channel, message) = self.try_receive_message_from::<M>(client_id)?;
+            if channel == message.channel().id() {
+                return Some(message);
+            }
+            bevy::log::warn!(
+                "Dropped message of client {client_id} received on unexpected channel {channel}"
+            );
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use crate::console::CommandAppExt;
+use crate::rng::Rng;
+use bevy::prelude::*;
+
+/// Simulates a bad network link on received messages, configured through console variables.
+/// Both sides condition what they receive, so each direction of the link is affected once.
+pub struct ConditionerPlugin;
+
+impl Plugin for ConditionerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LinkConditioner>()
+            .add_cvar("net_fakelag", |c: &mut LinkConditioner| &mut c.latency)
+            .add_cvar("net_fakejitter", |c: &mut LinkConditioner| &mut c.jitter)
+            .add_cvar("net_fakeloss", |c: &mut LinkConditioner| &mut c.loss)
+            .add_cvar("net_fakedup", |c: &mut LinkConditioner| &mut c.duplication)
+            .add_cvar("net_fakereorder", |c: &mut LinkConditioner| {
+                &mut c.reordering
+            });
+    }
+}
+
+/// Extra delay of reordered messages in milliseconds, long enough for a few later ones to overtake them.
+const REORDER_DELAY: f32 = 50.0;
+
+#[derive(Resource, Debug, Clone, Default)]
+pub struct LinkConditioner {
+    /// Added delay of every message, in milliseconds.
+    pub latency: f32,
+    /// Upper bound of a random delay added on top of the latency, in milliseconds.
+    pub jitter: f32,
+    /// Chance of an unreliable message to be dropped, in `[0, 1]`.
+    pub loss: f32,
+    /// Chance of an unreliable message to arrive twice, in `[0, 1]`.
+    pub duplication: f32,
+    /// Chance of an unreliable message to be held back, so later ones arrive first, in `[0, 1]`.
+    pub reordering: f32,
+}
+
+/// Messages of one connection, held back until the conditioner lets them through.
+#[derive(Debug)]
+pub struct LinkQueue<T> {
+    /// Messages with the time they are released at, in seconds.
+    messages: Vec<(f64, T)>,
+    /// Release time of the last reliable message, later ones must not overtake it.
+    last_reliable: f64,
+    rng: Rng,
+}
+
+impl<T> Default for LinkQueue<T> {
+    fn default() -> Self {
+        Self {
+            messages: Vec::new(),
+            last_reliable: 0.0,
+            // every link drops and delays its own messages, unrelated to the others
+            rng: Rng::from_entropy(),
+        }
+    }
+}
+
+impl<T: Clone> LinkQueue<T> {
+    /// Queues a message received at `now`. Only latency and jitter apply to reliable messages,
+    /// and those still arrive in order.
+    pub fn push(&mut self, conditioner: &LinkConditioner, now: f64, reliable: bool, message: T) {
+        let mut delay = conditioner.latency + self.rng.fraction() * conditioner.jitter;
+
+        if reliable {
+            let release = (now + delay as f64 / 1000.0).max(self.last_reliable);
+            self.last_reliable = release;
+            self.messages.push((release, message));
+            return;
+        }
+
+        if self.rng.chance(conditioner.loss) {
+            return;
+        }
+        if self.rng.chance(conditioner.reordering) {
+            delay += REORDER_DELAY;
+        }
+        if self.rng.chance(conditioner.duplication) {
+            let duplicate_delay = delay + self.rng.fraction() * conditioner.jitter;
+            self.messages
+                .push((now + duplicate_delay as f64 / 1000.0, message.clone()));
+        }
+        self.messages.push((now + delay as f64 / 1000.0, message));
+    }
+
+    /// Takes the next message which is due at `now`, in the order they are released.
+    pub fn pop(&mut self, now: f64) -> Option<T> {
+        let (index, _) = self
+            .messages
+            .iter()
+            .enumerate()
+            .filter(|(_, (release, _))| *release <= now)
+            .min_by(|(_, (a, _)), (_, (b, _))| a.total_cmp(b))?;
+        Some(self.messages.remove(index).1)
+    }
+}
@@ -0,0 +1,152 @@
+use bevy::prelude::*;
+use std::fmt::Display;
+use std::str::FromStr;
+use std::{fs, iter};
+
+/// Register commands as one-shot-systems, and call them through events.
+pub struct CommandPlugin;
+
+/// Shortcut for the command system argument input
+pub type Args = In<Vec<String>>;
+
+impl Plugin for CommandPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<CommandEvent>().add_command("exec", exec);
+    }
+}
+
+pub trait CommandAppExt {
+    fn add_command<M>(
+        &mut self,
+        name: &str,
+        system: impl IntoSystem<Args, (), M> + 'static,
+    ) -> &mut Self;
+
+    /// Adds a command which prints or sets a variable of a resource.
+    fn add_cvar<R: Resource, T: FromStr + Display + 'static>(
+        &mut self,
+        name: &str,
+        var: fn(&mut R) -> &mut T,
+    ) -> &mut Self;
+}
+
+#[derive(Debug, Event, Deref, DerefMut)]
+pub struct CommandEvent(Vec<String>);
+
+impl CommandEvent {
+    pub fn new(name: &str, args: Vec<&str>) -> Self {
+        Self(iter::once(name).chain(args).map(|a| a.to_owned()).collect())
+    }
+
+    /// Splits a line of text into a command and its arguments, `None` if the line is empty.
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut args = line.split_whitespace();
+        let name = args.next()?;
+        Some(Self::new(name, args.collect()))
+    }
+}
+
+impl CommandAppExt for App {
+    fn add_command<M>(
+        &mut self,
+        name: &str,
+        system: impl IntoSystem<Args, (), M> + 'static,
+    ) -> &mut Self {
+        let name = name.to_owned();
+        let system_id = self.register_system(system);
+
+        let update_system = move |mut commands: Commands, mut reader: EventReader<CommandEvent>| {
+            reader
+                .read()
+                .filter(|e| e.first() == Some(&name))
+                .for_each(|e| commands.run_system_with(system_id, (*e).clone()));
+        };
+
+        self.add_systems(Update, update_system)
+    }
+
+    fn add_cvar<R: Resource, T: FromStr + Display + 'static>(
+        &mut self,
+        name: &str,
+        var: fn(&mut R) -> &mut T,
+    ) -> &mut Self {
+        self.add_command(name, move |In(args): Args, mut resource: ResMut<R>| {
+            // only printing the value must not look like a change to whoever watches the resource
+            if cvar(&args, var(resource.bypass_change_detection())) {
+                resource.set_changed();
+            }
+        })
+    }
+}
+
+/// Runs every line of a file as a command.
+fn exec(In(args): Args, mut evw: EventWriter<CommandEvent>) {
+    let Some(path) = args.get(1) else {
+        warn!("Usage: exec <path>");
+        return;
+    };
+    let file = match fs::read_to_string(path) {
+        Ok(file) => file,
+        Err(err) => {
+            warn!("Could not read {path}: {err}");
+            return;
+        }
+    };
+
+    evw.write_batch(file.lines().filter_map(CommandEvent::parse));
+}
+
+/// Prints the value of a variable, or sets it if an argument is given. Returns whether it was set.
+fn cvar<T: FromStr + Display>(args: &[String], var: &mut T) -> bool {
+    let name = &args[0];
+    match args.get(1).map(|arg| arg.parse()) {
+        None => info!("{name} is {var}"),
+        Some(Ok(value)) => {
+            *var = value;
+            return true;
+        }
+        Some(Err(_)) => warn!("Invalid value for {name}"),
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Resource, Default)]
+    struct Settings {
+        volume: u32,
+    }
+
+    /// Whether [Settings] changed during the last update.
+    #[derive(Resource, Default)]
+    struct Changed(bool);
+
+    #[test]
+    fn printing_a_cvar_does_not_change_it() {
+        let mut app = App::new();
+        app.add_plugins(CommandPlugin)
+            .init_resource::<Settings>()
+            .init_resource::<Changed>()
+            .add_cvar("volume", |settings: &mut Settings| &mut settings.volume)
+            .add_systems(
+                Last,
+                |settings: Res<Settings>, mut changed: ResMut<Changed>| {
+                    changed.0 = settings.is_changed();
+                },
+            );
+        app.update();
+
+        app.world_mut()
+            .send_event(CommandEvent::new("volume", vec![]));
+        app.update();
+        assert!(!app.world().resource::<Changed>().0);
+
+        app.world_mut()
+            .send_event(CommandEvent::new("volume", vec!["7"]));
+        app.update();
+        assert!(app.world().resource::<Changed>().0);
+        assert_eq!(app.world().resource::<Settings>().volume, 7);
+    }
+}
@@ -1,2 +1,30 @@
+use std::env;
+use std::time::Duration;
+
 pub const TICK_RATE: usize = 60;
 pub const GAME_PORT: u16 = 5555;
+
+/// Bumped whenever messages change, clients and servers only talk to the same version.
+pub const PROTOCOL_VERSION: u32 = 12;
+/// Commit the game was built from.
+pub const BUILD_HASH: &str = env!("BUILD_HASH");
+
+/// How often both sides send a message, even with nothing else to say, to show they are alive.
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Folder assets are read from, relative to where Bevy looks for them: `BEVY_ASSET_ROOT` if set,
+/// the crate under cargo, and the executable's folder otherwise. The crates of client and server
+/// share the assets next to them, while a shipped executable has them in its own folder.
+pub fn asset_path() -> String {
+    if env::var_os("BEVY_ASSET_ROOT").is_none() && env::var_os("CARGO_MANIFEST_DIR").is_some() {
+        "../assets".to_owned()
+    } else {
+        "assets".to_owned()
+    }
+}
+
+/// Map loaded on startup, before any other is asked for.
+pub const DEFAULT_MAP: &str = "example";
+
+/// Pitch is kept just short of straight up and down, to avoid gimbal flips.
+pub const PITCH_LIMIT: f32 = std::f32::consts::FRAC_PI_2 - 0.01;
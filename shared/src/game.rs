@@ -0,0 +1,112 @@
+use crate::session::Team;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameMode {
+    #[default]
+    Deathmatch,
+    TeamDeathmatch,
+    CaptureTheFlag,
+}
+
+impl GameMode {
+    pub fn has_teams(&self) -> bool {
+        !matches!(self, Self::Deathmatch)
+    }
+}
+
+impl FromStr for GameMode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dm" => Ok(Self::Deathmatch),
+            "tdm" => Ok(Self::TeamDeathmatch),
+            "ctf" => Ok(Self::CaptureTheFlag),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Display for GameMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Deathmatch => "dm",
+            Self::TeamDeathmatch => "tdm",
+            Self::CaptureTheFlag => "ctf",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RoundState {
+    /// Players get ready, nothing counts yet.
+    #[default]
+    Warmup,
+    Live,
+    /// The round is decided, the scoreboard is shown until the next warmup.
+    Ended,
+}
+
+impl Display for RoundState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Warmup => "warmup",
+            Self::Live => "live",
+            Self::Ended => "ended",
+        })
+    }
+}
+
+/// Where the flag of a team stands in capture the flag.
+#[derive(Component, Debug, Clone, Copy)]
+#[require(Transform)]
+pub struct FlagStand(pub Team);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum FlagState {
+    Home,
+    Carried { by: String },
+    Dropped,
+}
+
+/// Where a flag is shown.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum FlagPlacement {
+    /// Lying at a position, at home or dropped.
+    At(Vec3),
+    /// Carried by the pawn of an actor.
+    Carried(u64),
+}
+
+/// Where the flags of capture the flag are, kept by the server and sent to clients whenever
+/// they move. Empty in other modes.
+#[derive(Resource, Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FlagPlacements(pub Vec<(Team, FlagPlacement)>);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlayerScore {
+    pub name: String,
+    pub team: Option<Team>,
+    pub kills: u32,
+    pub deaths: u32,
+    pub score: i32,
+}
+
+/// Everything clients show about the state of the game, kept by the server and sent to clients.
+#[derive(Resource, Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Scoreboard {
+    pub mode: GameMode,
+    pub state: RoundState,
+    /// Seconds until the round state changes, if it does on its own.
+    pub time_left: Option<f32>,
+    /// Scores of teams, only in modes with teams.
+    pub teams: Vec<(Team, i32)>,
+    /// Sorted by score, best first.
+    pub players: Vec<PlayerScore>,
+    /// Only in capture the flag.
+    pub flags: Vec<(Team, FlagState)>,
+}
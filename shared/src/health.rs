@@ -0,0 +1,94 @@
+use bevy::ecs::component::HookContext;
+use bevy::ecs::world::DeferredWorld;
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+/// Health and death of actors. Damage is only dealt by the server, clients follow its verdicts.
+pub struct HealthPlugin;
+
+impl Plugin for HealthPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<DamageEvent>().add_event::<DeathEvent>();
+    }
+}
+
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct Health {
+    pub current: f32,
+    pub max: f32,
+}
+
+impl Default for Health {
+    fn default() -> Self {
+        Self {
+            current: 100.0,
+            max: 100.0,
+        }
+    }
+}
+
+impl Health {
+    pub fn is_dead(&self) -> bool {
+        self.current <= 0.0
+    }
+}
+
+#[derive(Event, Debug, Clone, Copy)]
+pub struct DamageEvent {
+    pub target: Entity,
+    pub amount: f32,
+    /// Pawn which dealt the damage, if any.
+    pub source: Option<Entity>,
+}
+
+#[derive(Event, Debug, Clone, Copy)]
+pub struct DeathEvent {
+    pub target: Entity,
+    /// Pawn which dealt the final blow, if any.
+    pub source: Option<Entity>,
+}
+
+/// Placed on an actor whose health ran out. While dead, its character controller and collider
+/// are disabled, so it neither moves nor gets in the way.
+#[derive(Component, Debug, Default)]
+#[component(on_add = Dead::on_add, on_remove = Dead::on_remove)]
+pub struct Dead;
+
+/// Holds the character controller of a [Dead] actor, until it comes back to life.
+#[derive(Component)]
+struct DisabledController(KinematicCharacterController);
+
+impl Dead {
+    fn on_add(mut world: DeferredWorld, ctx: HookContext) {
+        let controller = world
+            .get::<KinematicCharacterController>(ctx.entity)
+            .cloned();
+        let mut commands = world.commands();
+        let mut entity = commands.entity(ctx.entity);
+        entity.insert(ColliderDisabled).remove::<(
+            KinematicCharacterController,
+            KinematicCharacterControllerOutput,
+        )>();
+        if let Some(controller) = controller {
+            entity.insert(DisabledController(controller));
+        }
+    }
+
+    fn on_remove(mut world: DeferredWorld, ctx: HookContext) {
+        let controller = world
+            .get::<DisabledController>(ctx.entity)
+            .map(|disabled| disabled.0.clone());
+        let mut commands = world.commands();
+        let mut entity = commands.entity(ctx.entity);
+        // also runs when the actor is despawned, so it must not insist on the entity existing
+        entity.try_remove::<(ColliderDisabled, DisabledController)>();
+        if let Some(controller) = controller {
+            entity.try_insert(controller);
+        }
+    }
+}
+
+/// Where actors (re)enter the game.
+#[derive(Component, Debug, Default)]
+#[require(Transform)]
+pub struct SpawnPoint;
@@ -41,6 +41,17 @@ pub trait Interpolate<T>: Component<Mutability = Mutable> {
     fn get_state(target: &T) -> Self::State;
     fn set_state(target: &mut T, state: Self::State);
     fn interpolate(start: &Self::State, end: &Self::State, weight: f32) -> Self::State;
+
+    /// Moves the target without interpolating from where it was, e.g. when it respawns.
+    fn teleport(&mut self, target: &mut T, state: Self::State)
+    where
+        Self::State: Clone,
+    {
+        let buffer = self.get_buffer_mut();
+        buffer.start = Some(state.clone());
+        buffer.end = Some(state.clone());
+        Self::set_state(target, state);
+    }
 }
 
 pub fn target<T: Component<Mutability = Mutable>, M: Interpolate<T>>(mut q: Query<(&T, &mut M)>) {
@@ -1,14 +1,29 @@
+use crate::consts::PITCH_LIMIT;
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
+pub mod channels;
+pub mod conditioner;
+pub mod console;
 pub mod consts;
+pub mod game;
+pub mod health;
 pub mod interpolate;
+pub mod maps;
+pub mod movers;
 pub mod pawns;
 pub mod plugins;
-pub mod scenes;
+pub mod projectiles;
+pub mod protocol;
+pub mod quantize;
+pub mod rng;
 pub mod session;
+pub mod weapons;
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Command {
+    /// Absolute view angles, yaw in `x` and pitch in `y`, in radians.
+    #[serde(with = "quantize::angles")]
     pub angle: Vec2,
     pub forward: bool,
     pub backward: bool,
@@ -20,4 +35,53 @@ pub struct Command {
     pub sneak: bool,
     pub crouch: bool,
     pub fire: bool,
+    pub reload: bool,
+    /// Uses what the pawn looks at, e.g. a button.
+    pub interact: bool,
+    /// Weapon slot to switch to, if any.
+    pub weapon: Option<u8>,
+    /// Presses and releases that happened during the tick, in order.
+    pub events: Vec<InputEvent>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum InputKind {
+    Jump,
+    Fire,
+}
+
+/// A timestamped change of a button, so e.g. shots can be evaluated more precisely than once per tick.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct InputEvent {
+    pub kind: InputKind,
+    pub pressed: bool,
+    /// Point in time within the tick, from `0` at its start to `1` at its end.
+    /// Buttons are read once per frame, so this is only as precise as the frame rate.
+    #[serde(with = "quantize::fraction")]
+    pub time: f32,
+}
+
+impl Command {
+    /// Unnormalized local movement direction, `-Z` is forward.
+    pub fn direction(&self) -> Vec3 {
+        let axis = |positive: bool, negative: bool| positive as i8 as f32 - negative as i8 as f32;
+        Vec3::new(
+            axis(self.right, self.left),
+            axis(self.up, self.down),
+            axis(self.backward, self.forward),
+        )
+    }
+
+    /// Times within the tick at which the given input was pressed.
+    pub fn presses(&self, kind: InputKind) -> impl Iterator<Item = f32> + '_ {
+        self.events
+            .iter()
+            .filter(move |e| e.kind == kind && e.pressed)
+            .map(|e| e.time)
+    }
+
+    /// View angles with the pitch clamped to [PITCH_LIMIT].
+    pub fn view_angles(&self) -> Vec2 {
+        Vec2::new(self.angle.x, self.angle.y.clamp(-PITCH_LIMIT, PITCH_LIMIT))
+    }
 }
@@ -0,0 +1,177 @@
+use bevy::gltf::{GltfMesh, GltfNode};
+use bevy::prelude::*;
+use bevy::render::mesh::{Indices, PrimitiveTopology, VertexAttributeValues};
+use bevy::render::render_asset::RenderAssetUsages;
+use bevy_rapier3d::prelude::*;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+/// Colliders generated from meshes are kept here between runs, one file per map version.
+const CACHE_PATH: &str = "cache/colliders";
+
+/// Collider shapes generated from the mesh of a node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeshShape {
+    /// The triangles as they are, for static geometry.
+    Trimesh,
+    /// Convex parts approximating the mesh, for dynamic bodies.
+    ConvexDecomposition,
+}
+
+impl MeshShape {
+    /// Shape asked for by the suffix of a node's name, `-col` for a trimesh and `-convcol`
+    /// for a convex decomposition.
+    pub fn from_name(name: &str) -> Option<Self> {
+        if name.ends_with("-convcol") {
+            Some(Self::ConvexDecomposition)
+        } else if name.ends_with("-col") {
+            Some(Self::Trimesh)
+        } else {
+            None
+        }
+    }
+
+    fn computed(self) -> ComputedColliderShape {
+        match self {
+            Self::Trimesh => ComputedColliderShape::TriMesh(TriMeshFlags::MERGE_DUPLICATE_VERTICES),
+            Self::ConvexDecomposition => {
+                ComputedColliderShape::ConvexDecomposition(VHACDParameters::default())
+            }
+        }
+    }
+}
+
+/// Colliders generated from the meshes of one version of a map, by the index of their node.
+#[derive(Default)]
+pub struct ColliderCache {
+    path: PathBuf,
+    colliders: HashMap<usize, Collider>,
+    changed: bool,
+}
+
+impl ColliderCache {
+    /// Reads what was generated for this version of the map before, if anything.
+    pub fn load(map: &str, hash: u64) -> Self {
+        let path = PathBuf::from(CACHE_PATH).join(format!("{map}-{hash:016x}.json"));
+        let colliders = fs::read(&path)
+            .ok()
+            .and_then(|bytes| {
+                serde_json::from_slice(&bytes)
+                    .inspect_err(|err| warn!("Ignored broken collider cache {path:?}: {err}"))
+                    .ok()
+            })
+            .unwrap_or_default();
+
+        Self {
+            path,
+            colliders,
+            changed: false,
+        }
+    }
+
+    /// The collider of the node's mesh, generated unless it is cached.
+    pub fn get_or_generate(
+        &mut self,
+        node: &GltfNode,
+        shape: MeshShape,
+        gltf_meshes: &Assets<GltfMesh>,
+        meshes: &Assets<Mesh>,
+    ) -> Option<Collider> {
+        if let Some(collider) = self.colliders.get(&node.index) {
+            return Some(collider.clone());
+        }
+
+        let Some(mesh) = node.mesh.as_ref().and_then(|mesh| gltf_meshes.get(mesh)) else {
+            warn!("Map node {} wants a collider, but has no mesh", node.name);
+            return None;
+        };
+        let collider = generate(
+            mesh.primitives
+                .iter()
+                .filter_map(|primitive| meshes.get(&primitive.mesh)),
+            shape,
+        )?;
+        self.colliders.insert(node.index, collider.clone());
+        self.changed = true;
+        Some(collider)
+    }
+
+    /// Writes the cache, if anything was generated since it was read.
+    pub fn save(&self) {
+        if !self.changed {
+            return;
+        }
+        let result = fs::create_dir_all(CACHE_PATH).and_then(|()| {
+            // shapes only deserialize from self-describing formats
+            let bytes = serde_json::to_vec(&self.colliders)?;
+            fs::write(&self.path, bytes)
+        });
+        if let Err(err) = result {
+            warn!("Could not write collider cache {:?}: {err}", self.path);
+        }
+    }
+}
+
+/// One collider for all given meshes, which are the primitives of a single glTF mesh.
+fn generate<'a>(meshes: impl Iterator<Item = &'a Mesh>, shape: MeshShape) -> Option<Collider> {
+    // compounds can't nest, so the parts are merged before the shape is computed
+    let mut positions = Vec::<[f32; 3]>::new();
+    let mut indices = Vec::<u32>::new();
+    for mesh in meshes {
+        let Some(VertexAttributeValues::Float32x3(mesh_positions)) =
+            mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+        else {
+            continue;
+        };
+        let offset = positions.len() as u32;
+        match mesh.indices() {
+            Some(mesh_indices) => indices.extend(mesh_indices.iter().map(|i| offset + i as u32)),
+            None => indices.extend(offset..offset + mesh_positions.len() as u32),
+        }
+        positions.extend(mesh_positions);
+    }
+    if indices.is_empty() {
+        return None;
+    }
+
+    let merged = Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::MAIN_WORLD,
+    )
+    .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+    .with_inserted_indices(Indices::U32(indices));
+    Collider::from_bevy_mesh(&merged, &shape.computed())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shapes_from_names() {
+        assert_eq!(MeshShape::from_name("wall-col"), Some(MeshShape::Trimesh));
+        assert_eq!(
+            MeshShape::from_name("crate-convcol"),
+            Some(MeshShape::ConvexDecomposition)
+        );
+        assert_eq!(MeshShape::from_name("column"), None);
+    }
+
+    #[test]
+    fn generated_colliders_survive_the_cache() {
+        let cuboid = Mesh::from(Cuboid::new(2.0, 1.0, 4.0));
+        let moved = cuboid.clone().translated_by(Vec3::X * 4.0);
+        let trimesh = generate([&cuboid, &moved].into_iter(), MeshShape::Trimesh).unwrap();
+        let convex = generate([&cuboid].into_iter(), MeshShape::ConvexDecomposition).unwrap();
+        assert!(trimesh.as_trimesh().is_some());
+        assert!(convex.as_compound().is_some());
+
+        let colliders = HashMap::from([(0, trimesh), (1, convex)]);
+        let bytes = serde_json::to_vec(&colliders).unwrap();
+        let decoded: HashMap<usize, Collider> = serde_json::from_slice(&bytes).unwrap();
+        let aabb = |collider: &Collider| collider.raw.compute_local_aabb();
+        assert_eq!(aabb(&decoded[&0]), aabb(&colliders[&0]));
+        assert_eq!(aabb(&decoded[&1]), aabb(&colliders[&1]));
+    }
+}
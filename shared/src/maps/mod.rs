@@ -0,0 +1,462 @@
+use crate::console::{Args, CommandAppExt};
+use crate::consts::DEFAULT_MAP;
+use crate::game::FlagStand;
+use crate::health::SpawnPoint;
+use crate::interpolate::InterpolateTransform;
+use crate::maps::colliders::{ColliderCache, MeshShape};
+use crate::movers::{Activation, Mover, MoverPath, MoverTrigger};
+use crate::session::Team;
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, LoadContext};
+use bevy::gltf::{Gltf, GltfMesh, GltfNode};
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use serde::Deserialize;
+use std::collections::HashSet;
+
+mod colliders;
+
+/// Levels, loaded from `.gltf` files in `assets/maps` by name. Nodes of the file become entities,
+/// and the JSON extras of a node tell what else it is, e.g. a collider or a spawn point.
+/// Nodes named `*-col` collide as their mesh, and `*-convcol` as its convex decomposition.
+pub struct MapPlugin;
+
+impl Plugin for MapPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<Map>()
+            .register_asset_loader(MapLoader)
+            .init_resource::<CurrentMap>()
+            .add_event::<LoadMapEvent>()
+            .add_event::<MapSpawnedEvent>()
+            .add_event::<MapFailedEvent>()
+            .add_systems(Startup, |mut evw: EventWriter<LoadMapEvent>| {
+                evw.write(LoadMapEvent(DEFAULT_MAP.to_owned()));
+            })
+            .add_command("map", change_map)
+            .add_systems(Update, (load_map, spawn_map).chain());
+    }
+}
+
+/// A map file, along with the hash of its contents to tell different versions of it apart.
+#[derive(Asset, TypePath, Debug)]
+pub struct Map {
+    pub gltf: Handle<Gltf>,
+    /// Only covers the file itself, buffers and textures should be embedded.
+    pub hash: u64,
+}
+
+#[derive(Default)]
+struct MapLoader;
+
+impl AssetLoader for MapLoader {
+    type Asset = Map;
+    type Settings = ();
+    type Error = std::io::Error;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        load_context: &mut LoadContext<'_>,
+    ) -> Result<Map, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        // the same file once more, as the glTF it is
+        let path = load_context.path().to_owned();
+        Ok(Map {
+            gltf: load_context.load(path),
+            hash: content_hash(&bytes),
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["gltf"]
+    }
+}
+
+/// FNV-1a, stable across builds and platforms, unlike the std hashers.
+fn content_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Unloads the current map, and loads the one of the given name.
+#[derive(Event, Debug, Clone)]
+pub struct LoadMapEvent(pub String);
+
+/// A map finished loading and is in the world now.
+#[derive(Event, Debug, Clone)]
+pub struct MapSpawnedEvent {
+    pub name: String,
+    pub hash: u64,
+}
+
+/// A map could not be loaded, e.g. because it does not exist here.
+#[derive(Event, Debug, Clone)]
+pub struct MapFailedEvent(pub String);
+
+/// The map in play, or being loaded.
+#[derive(Resource, Debug, Default)]
+pub struct CurrentMap {
+    pub name: String,
+    /// Hash of the map file, once it is spawned.
+    pub hash: Option<u64>,
+    handle: Handle<Map>,
+    root: Option<Entity>,
+}
+
+/// Placed on every entity spawned from a node of the map.
+#[derive(Component, Debug, Clone)]
+pub struct MapNode {
+    pub node: Handle<GltfNode>,
+    /// Index of the node in the file, the same on every side playing the map.
+    pub index: usize,
+}
+
+/// Light a node of the map casts, added by clients which render.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MapLight {
+    Directional { illuminance: f32, shadows: bool },
+    Point { intensity: f32, range: f32 },
+}
+
+/// What a node is, as read from its glTF extras, e.g.
+/// `{"collider": {"cuboid": {"half_extents": [1, 1, 1]}}, "body": "dynamic", "mass": 20}`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct NodeExtras {
+    spawn_point: bool,
+    flag_stand: Option<Team>,
+    collider: Option<ColliderShape>,
+    body: Body,
+    /// Mass of a dynamic body in kilograms, otherwise derived from its collider.
+    mass: Option<f32>,
+    light: Option<MapLight>,
+    /// Makes the node a kinematic body moving along a path, see [Mover].
+    mover: Option<MoverPath>,
+    trigger: Option<MoverTrigger>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ColliderShape {
+    Cuboid {
+        half_extents: Vec3,
+    },
+    Ball {
+        radius: f32,
+    },
+    Capsule {
+        half_height: f32,
+        radius: f32,
+    },
+    /// Generated from the node's mesh, see [MeshShape::Trimesh].
+    Trimesh,
+    /// Generated from the node's mesh, see [MeshShape::ConvexDecomposition].
+    ConvexDecomposition,
+}
+
+/// Assets a map is spawned from, and the colliders generated from its meshes.
+struct MapAssets<'a> {
+    nodes: &'a Assets<GltfNode>,
+    gltf_meshes: &'a Assets<GltfMesh>,
+    meshes: &'a Assets<Mesh>,
+    colliders: ColliderCache,
+}
+
+impl MapAssets<'_> {
+    fn collider(&mut self, node: &GltfNode, shape: &ColliderShape) -> Option<Collider> {
+        let mesh_shape = match *shape {
+            ColliderShape::Cuboid { half_extents } => {
+                return Some(Collider::cuboid(
+                    half_extents.x,
+                    half_extents.y,
+                    half_extents.z,
+                ));
+            }
+            ColliderShape::Ball { radius } => return Some(Collider::ball(radius)),
+            ColliderShape::Capsule {
+                half_height,
+                radius,
+            } => return Some(Collider::capsule_y(half_height, radius)),
+            ColliderShape::Trimesh => MeshShape::Trimesh,
+            ColliderShape::ConvexDecomposition => MeshShape::ConvexDecomposition,
+        };
+        self.colliders
+            .get_or_generate(node, mesh_shape, self.gltf_meshes, self.meshes)
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Body {
+    #[default]
+    Fixed,
+    Dynamic,
+}
+
+fn change_map(In(args): Args, mut evw: EventWriter<LoadMapEvent>) {
+    let Some(name) = args.get(1) else {
+        warn!("Usage: map <name>");
+        return;
+    };
+    evw.write(LoadMapEvent(name.clone()));
+}
+
+fn load_map(
+    mut commands: Commands,
+    mut evr_load: EventReader<LoadMapEvent>,
+    mut current: ResMut<CurrentMap>,
+    asset_server: Res<AssetServer>,
+) {
+    let Some(LoadMapEvent(name)) = evr_load.read().last() else {
+        return;
+    };
+
+    info!("Loading map {name}");
+    if let Some(root) = current.root.take() {
+        commands.entity(root).despawn();
+    }
+    *current = CurrentMap {
+        name: name.clone(),
+        hash: None,
+        handle: asset_server.load(format!("maps/{name}.gltf")),
+        root: None,
+    };
+}
+
+#[allow(clippy::too_many_arguments)]
+fn spawn_map(
+    mut commands: Commands,
+    mut current: ResMut<CurrentMap>,
+    mut evw_spawned: EventWriter<MapSpawnedEvent>,
+    mut evw_failed: EventWriter<MapFailedEvent>,
+    asset_server: Res<AssetServer>,
+    maps: Res<Assets<Map>>,
+    gltfs: Res<Assets<Gltf>>,
+    nodes: Res<Assets<GltfNode>>,
+    gltf_meshes: Res<Assets<GltfMesh>>,
+    meshes: Res<Assets<Mesh>>,
+) {
+    if current.hash.is_some() || current.name.is_empty() {
+        return;
+    }
+    if asset_server.load_state(&current.handle).is_failed() {
+        error!("Could not load map {}", current.name);
+        evw_failed.write(MapFailedEvent(std::mem::take(&mut current.name)));
+        return;
+    }
+    if !asset_server.is_loaded_with_dependencies(&current.handle) {
+        return;
+    }
+    let Some((map, gltf)) = maps
+        .get(&current.handle)
+        .and_then(|map| Some((map, gltfs.get(&map.gltf)?)))
+    else {
+        return;
+    };
+    let mut assets = MapAssets {
+        nodes: &nodes,
+        gltf_meshes: &gltf_meshes,
+        meshes: &meshes,
+        colliders: ColliderCache::load(&current.name, map.hash),
+    };
+
+    let root = commands
+        .spawn((
+            Name::new(current.name.clone()),
+            Transform::default(),
+            Visibility::default(),
+        ))
+        .id();
+    // the top nodes are the ones which are no other's child
+    let children = gltf
+        .nodes
+        .iter()
+        .filter_map(|node| nodes.get(node))
+        .flat_map(|node| node.children.iter().map(Handle::id))
+        .collect::<HashSet<_>>();
+    for node in gltf
+        .nodes
+        .iter()
+        .filter(|node| !children.contains(&node.id()))
+    {
+        spawn_node(&mut commands, &mut assets, node, root);
+    }
+    assets.colliders.save();
+
+    let hash = map.hash;
+    info!("Spawned map {} ({hash:016x})", current.name);
+    current.hash = Some(hash);
+    current.root = Some(root);
+    evw_spawned.write(MapSpawnedEvent {
+        name: current.name.clone(),
+        hash,
+    });
+}
+
+fn spawn_node(
+    commands: &mut Commands,
+    assets: &mut MapAssets,
+    handle: &Handle<GltfNode>,
+    parent: Entity,
+) {
+    let Some(node) = assets.nodes.get(handle) else {
+        return;
+    };
+    let extras = node
+        .extras
+        .as_ref()
+        .map(|extras| serde_json::from_str::<NodeExtras>(&extras.value))
+        .transpose()
+        .unwrap_or_else(|err| {
+            warn!("Ignored invalid extras of map node {}: {err}", node.name);
+            None
+        })
+        .unwrap_or_default();
+
+    let mut entity = commands.spawn((
+        Name::new(node.name.clone()),
+        node.transform,
+        MapNode {
+            node: handle.clone(),
+            index: node.index,
+        },
+        ChildOf(parent),
+    ));
+    if extras.spawn_point {
+        entity.insert(SpawnPoint);
+    }
+    if let Some(team) = extras.flag_stand {
+        entity.insert(FlagStand(team));
+    }
+    let shape = extras
+        .collider
+        .or_else(|| match MeshShape::from_name(&node.name)? {
+            MeshShape::Trimesh => Some(ColliderShape::Trimesh),
+            MeshShape::ConvexDecomposition => Some(ColliderShape::ConvexDecomposition),
+        });
+    if let Some(collider) = shape.and_then(|shape| assets.collider(node, &shape)) {
+        entity.insert(collider);
+    }
+    if let Body::Dynamic = extras.body {
+        entity.insert((
+            RigidBody::Dynamic,
+            Velocity::default(),
+            InterpolateTransform::default(),
+        ));
+    }
+    if let Some(mass) = extras.mass {
+        entity.insert(ColliderMassProperties::Mass(mass));
+    }
+    if let Some(light) = extras.light {
+        entity.insert(light);
+    }
+    if let Some(path) = extras.mover {
+        entity.insert(Mover::new(node.transform, path));
+    }
+    if let Some(trigger) = extras.trigger {
+        if trigger.on == Activation::Touch {
+            entity.insert(Sensor);
+        }
+        entity.insert(trigger);
+    }
+
+    let entity = entity.id();
+    for child in &node.children {
+        spawn_node(commands, assets, child, entity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::console::CommandPlugin;
+    use crate::consts::asset_path;
+    use bevy::gltf::GltfPlugin;
+    use bevy::render::mesh::MeshPlugin;
+    use bevy::scene::ScenePlugin;
+
+    #[test]
+    fn spawns_the_default_map() {
+        let mut app = App::new();
+        app.add_plugins((
+            TaskPoolPlugin::default(),
+            AssetPlugin {
+                file_path: asset_path(),
+                ..Default::default()
+            },
+            ScenePlugin,
+            MeshPlugin,
+            GltfPlugin::default(),
+            CommandPlugin,
+            MapPlugin,
+        ))
+        .init_asset::<Image>()
+        .init_asset::<StandardMaterial>();
+        app.finish();
+        app.cleanup();
+
+        for _ in 0..1000 {
+            app.update();
+            if app.world().resource::<CurrentMap>().hash.is_some() {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        app.update();
+
+        let world = app.world_mut();
+        assert_eq!(world.resource::<CurrentMap>().name, DEFAULT_MAP);
+        assert_eq!(
+            world
+                .query_filtered::<(), With<SpawnPoint>>()
+                .iter(world)
+                .count(),
+            4
+        );
+        assert_eq!(world.query::<&FlagStand>().iter(world).count(), 2);
+        let bodies = world
+            .query_filtered::<(&RigidBody, &Collider), Without<Mover>>()
+            .iter(world)
+            .count();
+        assert_eq!(bodies, 1);
+        assert_eq!(world.query::<&Mover>().iter(world).count(), 3);
+        assert!(
+            world
+                .query::<(&MoverTrigger, Has<Sensor>)>()
+                .iter(world)
+                .all(|(trigger, sensor)| sensor == (trigger.on == Activation::Touch))
+        );
+        // named for a collider generated from its mesh
+        assert!(world.query::<(&Name, &Collider)>().iter(world).any(
+            |(name, collider)| name.as_str() == "ground-col" && collider.as_trimesh().is_some()
+        ));
+    }
+
+    #[test]
+    fn reads_node_extras() {
+        let extras: NodeExtras = serde_json::from_str(
+            r#"{"collider": {"cuboid": {"half_extents": [1, 2, 3]}}, "body": "dynamic", "mass": 20, "flag_stand": "red"}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            extras.collider,
+            Some(ColliderShape::Cuboid { half_extents }) if half_extents == Vec3::new(1.0, 2.0, 3.0)
+        ));
+        assert!(matches!(extras.body, Body::Dynamic));
+        assert_eq!(extras.mass, Some(20.0));
+        assert_eq!(extras.flag_stand, Some(Team::Red));
+        assert!(!extras.spawn_point);
+
+        assert!(serde_json::from_str::<NodeExtras>(r#"{"spawnpoint": true}"#).is_err());
+    }
+
+    #[test]
+    fn content_hash_is_stable() {
+        assert_eq!(content_hash(b""), 0xcbf29ce484222325);
+        assert_eq!(content_hash(b"a"), 0xaf63dc4c8601ec8c);
+    }
+}
@@ -0,0 +1,365 @@
+use crate::health::Dead;
+use crate::interpolate::InterpolateTransform;
+use crate::pawns::fps::{FirstPersonPawn, simulate_system};
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Doors, platforms, elevators and anything else of a map which moves along a fixed path.
+/// Movers only advance with fixed ticks, so the same state moves them alike on every side.
+pub struct MoverPlugin;
+
+impl Plugin for MoverPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(FixedUpdate, move_movers.after(simulate_system));
+    }
+}
+
+/// How far below a pawn a mover may be for the pawn to ride on it.
+const GROUND_DISTANCE: f32 = 0.1;
+
+/// Pose of a mover at a point in time along its path, relative to where the map placed it.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Keyframe {
+    /// Seconds since the start of the path.
+    pub time: f32,
+    /// Offset in the space of the mover's parent.
+    #[serde(default)]
+    pub translation: Vec3,
+    /// Rotation around the mover's own origin.
+    #[serde(default)]
+    pub rotation: Quat,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MoverMode {
+    /// Every activation sends the mover the other way, e.g. a door which stays open.
+    #[default]
+    Toggle,
+    /// Activations send the mover to the end, where it waits before it returns, e.g. an elevator.
+    Return,
+    /// Runs along its path over and over, from the start, e.g. a platform.
+    Loop,
+}
+
+/// The path of a mover, as read from the extras of a map node, e.g.
+/// `{"keyframes": [{"time": 0}, {"time": 2, "translation": [0, 3, 0]}], "mode": "return", "wait": 2}`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MoverPath {
+    /// Sorted by time, the first one should be at `0`.
+    pub keyframes: Vec<Keyframe>,
+    #[serde(default)]
+    pub mode: MoverMode,
+    /// Seconds a [MoverMode::Return] mover waits at the end.
+    #[serde(default)]
+    pub wait: f32,
+}
+
+impl MoverPath {
+    /// Seconds from the start to the end of the path.
+    pub fn duration(&self) -> f32 {
+        self.keyframes.last().map_or(0.0, |keyframe| keyframe.time)
+    }
+
+    /// Offset and rotation at the given time, in between keyframes they are blended.
+    pub fn sample(&self, time: f32) -> (Vec3, Quat) {
+        let next = self
+            .keyframes
+            .partition_point(|keyframe| keyframe.time <= time);
+        match (
+            self.keyframes.get(next.wrapping_sub(1)),
+            self.keyframes.get(next),
+        ) {
+            (Some(from), Some(to)) => {
+                let weight = (time - from.time) / (to.time - from.time);
+                (
+                    from.translation.lerp(to.translation, weight),
+                    from.rotation.slerp(to.rotation, weight),
+                )
+            }
+            (Some(keyframe), None) | (None, Some(keyframe)) => {
+                (keyframe.translation, keyframe.rotation)
+            }
+            (None, None) => (Vec3::ZERO, Quat::IDENTITY),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum MoverPhase {
+    #[default]
+    Resting,
+    Forward,
+    /// At the end of its path, until it returns.
+    Waiting {
+        left: f32,
+    },
+    Backward,
+}
+
+/// Everything that changes about a mover, which the server sends to clients.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct MoverState {
+    /// Seconds along the path.
+    pub time: f32,
+    pub phase: MoverPhase,
+}
+
+/// A kinematic body moved along a [MoverPath], carrying the pawns standing on it.
+#[derive(Component, Debug, Clone)]
+#[require(
+    RigidBody = RigidBody::KinematicPositionBased,
+    InterpolateTransform
+)]
+pub struct Mover {
+    /// Where the map placed the mover, its path is relative to it.
+    pub home: Transform,
+    pub path: MoverPath,
+    pub state: MoverState,
+}
+
+impl Mover {
+    pub fn new(home: Transform, path: MoverPath) -> Self {
+        let phase = match path.mode {
+            MoverMode::Loop => MoverPhase::Forward,
+            MoverMode::Toggle | MoverMode::Return => MoverPhase::Resting,
+        };
+        Self {
+            home,
+            path,
+            state: MoverState { time: 0.0, phase },
+        }
+    }
+
+    /// Sets the mover in motion, returns whether that changed anything.
+    pub fn activate(&mut self) -> bool {
+        let at_start = self.state.time <= 0.0;
+        let phase = match (self.path.mode, self.state.phase) {
+            (MoverMode::Loop, _) => return false,
+            (MoverMode::Toggle, MoverPhase::Resting) if at_start => MoverPhase::Forward,
+            (MoverMode::Toggle, MoverPhase::Resting | MoverPhase::Forward) => MoverPhase::Backward,
+            (MoverMode::Return, MoverPhase::Forward) => return false,
+            // held open for longer
+            (MoverMode::Return, MoverPhase::Waiting { .. }) => MoverPhase::Waiting {
+                left: self.path.wait,
+            },
+            (_, _) => MoverPhase::Forward,
+        };
+        let changed = phase != self.state.phase;
+        self.state.phase = phase;
+        changed
+    }
+
+    /// Moves the mover along its path, as far as it gets in `delta` seconds.
+    pub fn advance(&mut self, delta: f32) {
+        let duration = self.path.duration();
+        let state = &mut self.state;
+        match state.phase {
+            MoverPhase::Resting => {}
+            MoverPhase::Forward => {
+                state.time += delta;
+                if state.time >= duration {
+                    match self.path.mode {
+                        MoverMode::Toggle => {
+                            state.time = duration;
+                            state.phase = MoverPhase::Resting;
+                        }
+                        MoverMode::Return => {
+                            state.time = duration;
+                            state.phase = MoverPhase::Waiting {
+                                left: self.path.wait,
+                            };
+                        }
+                        MoverMode::Loop => state.time = (state.time - duration).min(duration),
+                    }
+                }
+            }
+            MoverPhase::Waiting { left } => {
+                state.phase = if left > delta {
+                    MoverPhase::Waiting { left: left - delta }
+                } else {
+                    MoverPhase::Backward
+                };
+            }
+            MoverPhase::Backward => {
+                state.time -= delta;
+                if state.time <= 0.0 {
+                    state.time = 0.0;
+                    state.phase = MoverPhase::Resting;
+                }
+            }
+        }
+    }
+
+    /// Where the mover is at its current point of the path, relative to its parent.
+    pub fn pose(&self) -> Transform {
+        let (translation, rotation) = self.path.sample(self.state.time);
+        Transform {
+            translation: self.home.translation + translation,
+            rotation: rotation * self.home.rotation,
+            scale: self.home.scale,
+        }
+    }
+}
+
+/// Advances all movers, and moves the pawns standing on them along through their controllers.
+#[allow(clippy::type_complexity)]
+pub fn move_movers(
+    rapier: ReadRapierContext,
+    time: Res<Time>,
+    mut q_movers: Query<(Entity, &mut Mover, &mut Transform, Option<&ChildOf>)>,
+    q_parents: Query<&GlobalTransform>,
+    mut q_pawns: Query<
+        (
+            Entity,
+            &Transform,
+            &Collider,
+            &mut KinematicCharacterController,
+        ),
+        (With<FirstPersonPawn>, Without<Mover>, Without<Dead>),
+    >,
+) -> Result {
+    let context = rapier.single()?;
+    // what pawns stand on is looked up before anything moves, where the physics world has it
+    let riders = q_pawns
+        .iter()
+        .filter_map(|(pawn, transform, collider, _)| {
+            let filter = QueryFilter::default()
+                .exclude_collider(pawn)
+                .exclude_sensors();
+            let (ground, _) = context.cast_shape(
+                transform.translation,
+                transform.rotation,
+                Vec3::NEG_Y,
+                collider,
+                ShapeCastOptions::with_max_time_of_impact(GROUND_DISTANCE),
+                filter,
+            )?;
+            q_movers.contains(ground).then_some((pawn, ground))
+        })
+        .collect::<Vec<_>>();
+
+    let mut moves = HashMap::new();
+    for (entity, mut mover, mut transform, child_of) in q_movers.iter_mut() {
+        let before = *transform;
+        mover.advance(time.delta_secs());
+        *transform = mover.pose();
+
+        let parent = child_of
+            .and_then(|child_of| q_parents.get(child_of.parent()).ok())
+            .map(GlobalTransform::compute_transform)
+            .unwrap_or_default();
+        moves.insert(
+            entity,
+            (
+                parent.mul_transform(before),
+                parent.mul_transform(*transform),
+            ),
+        );
+    }
+
+    for (pawn, ground) in riders {
+        let Some((before, after)) = moves.get(&ground) else {
+            continue;
+        };
+        let Ok((_, transform, _, mut controller)) = q_pawns.get_mut(pawn) else {
+            continue;
+        };
+        // the point the pawn stands at goes wherever the mover takes it
+        let local = before
+            .compute_affine()
+            .inverse()
+            .transform_point3(transform.translation);
+        let carried = after.transform_point(local) - transform.translation;
+        controller.translation = Some(controller.translation.unwrap_or_default() + carried);
+    }
+    Ok(())
+}
+
+/// Activates the movers named in `targets` when a pawn touches or uses the node it is placed on.
+#[derive(Component, Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MoverTrigger {
+    pub on: Activation,
+    /// Names of the nodes of the movers.
+    pub targets: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Activation {
+    /// A pawn enters the node's collider, which is made a sensor.
+    Touch,
+    /// A pawn looks at the node's collider and uses it.
+    Use,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn elevator(mode: MoverMode) -> Mover {
+        Mover::new(
+            Transform::from_xyz(0.0, 1.0, 0.0),
+            MoverPath {
+                keyframes: vec![
+                    Keyframe {
+                        time: 0.0,
+                        translation: Vec3::ZERO,
+                        rotation: Quat::IDENTITY,
+                    },
+                    Keyframe {
+                        time: 2.0,
+                        translation: Vec3::new(0.0, 4.0, 0.0),
+                        rotation: Quat::IDENTITY,
+                    },
+                ],
+                mode,
+                wait: 1.0,
+            },
+        )
+    }
+
+    #[test]
+    fn samples_between_keyframes() {
+        let mover = elevator(MoverMode::Toggle);
+        assert_eq!(mover.path.sample(-1.0).0, Vec3::ZERO);
+        assert_eq!(mover.path.sample(0.5).0, Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(mover.path.sample(3.0).0, Vec3::new(0.0, 4.0, 0.0));
+        assert_eq!(mover.pose().translation, Vec3::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn return_movers_wait_and_come_back() {
+        let mut mover = elevator(MoverMode::Return);
+        assert!(mover.activate());
+        mover.advance(2.5);
+        assert_eq!(mover.state.time, 2.0);
+        assert_eq!(mover.state.phase, MoverPhase::Waiting { left: 1.0 });
+        assert!(!mover.activate());
+        mover.advance(1.0);
+        assert_eq!(mover.state.phase, MoverPhase::Backward);
+        mover.advance(3.0);
+        assert_eq!(mover.state, MoverState::default());
+    }
+
+    #[test]
+    fn toggle_movers_turn_around() {
+        let mut mover = elevator(MoverMode::Toggle);
+        assert!(mover.activate());
+        mover.advance(1.0);
+        assert!(mover.activate());
+        assert_eq!(mover.state.phase, MoverPhase::Backward);
+        mover.advance(1.0);
+        assert_eq!(mover.state, MoverState::default());
+
+        let mut platform = elevator(MoverMode::Loop);
+        assert!(!platform.activate());
+        platform.advance(2.5);
+        assert_eq!(platform.state.time, 0.5);
+    }
+}
@@ -1,58 +1,20 @@
 use crate::interpolate::{InterpolateRotation, InterpolateTranslation};
+use crate::pawns::{Pawn, PawnAppExt};
 use bevy::prelude::*;
-use std::f32::consts::FRAC_PI_2;
 
 pub struct FlyPawnPlugin;
 
 impl Plugin for FlyPawnPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(FixedUpdate, simulate_system);
+        app.register_pawn::<FlyPawn>()
+            .add_systems(FixedUpdate, simulate_system);
     }
 }
 
 #[derive(Component, Default)]
 pub struct FlyPawnCommand {
     angle: Vec2,
-    forward: bool,
-    backward: bool,
-    left: bool,
-    right: bool,
-    up: bool,
-    down: bool,
-}
-
-impl FlyPawnCommand {
-    pub fn apply(&mut self, command: &crate::Command) {
-        self.angle = command.angle;
-        self.forward = command.forward;
-        self.backward = command.backward;
-        self.left = command.left;
-        self.right = command.right;
-        self.up = command.up;
-        self.down = command.down;
-    }
-    fn direction(&self) -> Vec3 {
-        let mut direction = Vec3::ZERO;
-        if self.forward {
-            direction.z -= 1.0;
-        }
-        if self.backward {
-            direction.z += 1.0;
-        }
-        if self.right {
-            direction.x += 1.0;
-        }
-        if self.left {
-            direction.x -= 1.0;
-        }
-        if self.up {
-            direction.y += 1.0;
-        }
-        if self.down {
-            direction.y -= 1.0;
-        }
-        direction.normalize_or_zero()
-    }
+    direction: Vec3,
 }
 
 #[derive(Component)]
@@ -61,18 +23,31 @@ pub struct FlyPawn {
     pub speed: f32,
 }
 
-const PITCH_LIMIT: f32 = FRAC_PI_2 - 0.01;
+impl Pawn for FlyPawn {
+    type Command = FlyPawnCommand;
+
+    fn command(command: &crate::Command) -> Self::Command {
+        FlyPawnCommand {
+            angle: command.view_angles(),
+            direction: command.direction().normalize_or_zero(),
+        }
+    }
+
+    fn release(command: &Self::Command) -> Self::Command {
+        FlyPawnCommand {
+            angle: command.angle,
+            ..Default::default()
+        }
+    }
+}
 
 pub fn simulate_system(mut q: Query<(&FlyPawn, &FlyPawnCommand, &mut Transform)>, time: Res<Time>) {
     for (pawn, command, mut transform) in q.iter_mut() {
+        // rotation
+        transform.rotation = Quat::from_euler(EulerRot::YXZ, command.angle.x, command.angle.y, 0.0);
+
         // translation
-        let wish_direction = transform.rotation.mul_vec3(command.direction());
+        let wish_direction = transform.rotation.mul_vec3(command.direction);
         transform.translation += wish_direction * pawn.speed * time.delta_secs();
-
-        // rotation
-        let (yaw, pitch, roll) = transform.rotation.to_euler(EulerRot::YXZ);
-        let yaw = yaw - command.angle.x;
-        let pitch = (pitch - command.angle.y).clamp(-PITCH_LIMIT, PITCH_LIMIT);
-        transform.rotation = Quat::from_euler(EulerRot::YXZ, yaw, pitch, roll);
     }
 }
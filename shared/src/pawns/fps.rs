@@ -1,54 +1,34 @@
+use crate::InputKind;
+use crate::health::{Dead, Health};
 use crate::interpolate::InterpolateTranslation;
+use crate::pawns::{Pawn, PawnAppExt};
+use crate::weapons::Loadout;
 use bevy::prelude::*;
 use bevy_rapier3d::prelude::*;
-use std::f32::consts::FRAC_PI_2;
 
 pub struct FirstPersonPawnPlugin;
 
 impl Plugin for FirstPersonPawnPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(
-            FixedUpdate,
-            (simulate_system, write_transform_system).chain(),
-        );
+        app.register_pawn::<FirstPersonPawn>()
+            .add_systems(FixedUpdate, (read_output_system, simulate_system).chain());
     }
 }
 
 #[derive(Component, Default)]
 pub struct FirstPersonPawnCommand {
     pub angle: Vec2,
-    pub forward: bool,
-    pub backward: bool,
-    pub left: bool,
-    pub right: bool,
+    pub direction: Vec3,
     pub jump: bool,
-}
-
-impl FirstPersonPawnCommand {
-    pub fn apply(&mut self, command: &crate::Command) {
-        self.angle = command.angle;
-        self.forward = command.forward;
-        self.backward = command.backward;
-        self.left = command.left;
-        self.right = command.right;
-        self.jump = command.jump;
-    }
-    fn direction(&self) -> Vec3 {
-        let mut direction = Vec3::ZERO;
-        if self.forward {
-            direction.z -= 1.0;
-        }
-        if self.backward {
-            direction.z += 1.0;
-        }
-        if self.right {
-            direction.x += 1.0;
-        }
-        if self.left {
-            direction.x -= 1.0;
-        }
-        direction.normalize_or_zero()
-    }
+    /// Whether the trigger is held.
+    pub fire: bool,
+    /// When the trigger was first pulled during the tick, even if it was let go again,
+    /// from `0` at its start to `1` at its end.
+    pub fire_pressed: Option<f32>,
+    pub reload: bool,
+    pub interact: bool,
+    /// Weapon slot to switch to.
+    pub weapon: Option<u8>,
 }
 
 #[derive(Debug, Component)]
@@ -58,9 +38,13 @@ impl FirstPersonPawnCommand {
     Transform = default_transform(),
     Velocity,
     Collider = default_collider(),
-    InterpolateTranslation
+    InterpolateTranslation,
+    Health,
+    Loadout
 )]
 pub struct FirstPersonPawn {
+    pub yaw: f32,
+    pub pitch: f32,
     pub grounded: bool,
     pub acceleration: f32,
     pub jump_force: f32,
@@ -70,14 +54,58 @@ pub struct FirstPersonPawn {
 impl Default for FirstPersonPawn {
     fn default() -> Self {
         Self {
+            yaw: 0.0,
+            pitch: 0.0,
             grounded: false,
             damping: 20.0,
-            jump_force: 5.0,
-            acceleration: 20.0,
+            jump_force: 7.0,
+            acceleration: 40.0,
+        }
+    }
+}
+
+impl Pawn for FirstPersonPawn {
+    type Command = FirstPersonPawnCommand;
+
+    fn command(command: &crate::Command) -> Self::Command {
+        FirstPersonPawnCommand {
+            angle: command.view_angles(),
+            direction: command.direction().with_y(0.0).normalize_or_zero(),
+            jump: command.jump,
+            fire: command.fire,
+            fire_pressed: command.presses(InputKind::Fire).next(),
+            reload: command.reload,
+            interact: command.interact,
+            weapon: command.weapon,
+        }
+    }
+
+    fn release(command: &Self::Command) -> Self::Command {
+        FirstPersonPawnCommand {
+            angle: command.angle,
+            ..Default::default()
         }
     }
 }
 
+impl FirstPersonPawn {
+    /// Rotation around the vertical axis only, used for movement.
+    pub fn yaw_rotation(&self) -> Quat {
+        Quat::from_rotation_y(self.yaw)
+    }
+
+    /// Full view rotation, used to orient an attached camera.
+    pub fn view_rotation(&self) -> Quat {
+        Quat::from_euler(EulerRot::YXZ, self.yaw, self.pitch, 0.0)
+    }
+}
+
+/// Offset of the eyes from the pawn's origin, where cameras are placed.
+pub const EYE_OFFSET: Vec3 = Vec3::new(0.0, 0.5, 0.0);
+
+/// Mass pawns push with, and which impulses on them are divided by.
+pub const PAWN_MASS: f32 = 60.0;
+
 fn default_transform() -> Transform {
     Transform::from_xyz(0.0, 1.0, 0.0)
 }
@@ -88,73 +116,119 @@ fn default_collider() -> Collider {
 
 fn default_controller() -> KinematicCharacterController {
     KinematicCharacterController {
-        custom_mass: Some(60.0),
+        custom_mass: Some(PAWN_MASS),
         ..Default::default()
     }
 }
 
-const PITCH_LIMIT: f32 = FRAC_PI_2 - 0.01;
-
-fn simulate_system(
-    mut q_pawn: Query<(
-        &FirstPersonPawn,
+pub fn simulate_system(
+    mut q: Query<(
+        &mut FirstPersonPawn,
         &FirstPersonPawnCommand,
         &mut Velocity,
         &mut KinematicCharacterController,
-        &Children,
     )>,
-    mut q_camera: Query<&mut Transform, With<Camera3d>>,
     time: Res<Time>,
 ) {
     let delta_seconds = time.delta_secs();
-    for (pawn, command, mut velocity, mut controller, children) in q_pawn.iter_mut() {
-        for child in children.iter() {
-            let Some(mut camera) = q_camera.get_mut(child).ok() else {
-                continue;
-            };
-
-            // rotation
-            let (yaw, pitch, roll) = camera.rotation.to_euler(EulerRot::YXZ);
-            let yaw = yaw - command.angle.x;
-            let pitch = (pitch - command.angle.y).clamp(-PITCH_LIMIT, PITCH_LIMIT);
-            camera.rotation = Quat::from_euler(EulerRot::YXZ, yaw, pitch, roll);
-
-            // gravity
-            if !pawn.grounded {
-                velocity.linvel.y -= 9.81 * delta_seconds;
-            } else {
-                velocity.linvel.y = velocity.linvel.y.max(0.0);
-            }
-
-            // jumping
-            if pawn.grounded && command.jump {
-                velocity.linvel.y = pawn.jump_force;
-            }
-
-            // friction
-            velocity.linvel.x *= 1.0 - pawn.damping * delta_seconds;
-            velocity.linvel.z *= 1.0 - pawn.damping * delta_seconds;
-
-            // movement
-            let wish_direction =
-                Quat::from_euler(EulerRot::YXZ, yaw, 0.0, 0.0).mul_vec3(command.direction());
-            velocity.linvel += wish_direction * pawn.acceleration * delta_seconds;
-
-            // physics command
-            controller.translation = Some(velocity.linvel * delta_seconds);
+    for (mut pawn, command, mut velocity, mut controller) in q.iter_mut() {
+        // rotation
+        pawn.yaw = command.angle.x;
+        pawn.pitch = command.angle.y;
+
+        // gravity
+        if !pawn.grounded {
+            velocity.linvel.y -= 9.81 * delta_seconds;
+        } else {
+            velocity.linvel.y = velocity.linvel.y.max(0.0);
+        }
+
+        // jumping
+        if pawn.grounded && command.jump {
+            velocity.linvel.y = pawn.jump_force;
         }
+
+        // friction
+        velocity.linvel.x *= 1.0 - pawn.damping * delta_seconds;
+        velocity.linvel.z *= 1.0 - pawn.damping * delta_seconds;
+
+        // movement
+        let wish_direction = pawn.yaw_rotation().mul_vec3(command.direction);
+        velocity.linvel += wish_direction * pawn.acceleration * delta_seconds;
+
+        // physics command
+        controller.translation = Some(velocity.linvel * delta_seconds);
     }
 }
 
-fn write_transform_system(
-    mut q: Query<(
-        &mut FirstPersonPawn,
-        &mut Transform,
-        &KinematicCharacterControllerOutput,
-    )>,
+/// The controller moves the transform itself, only whether it ended up on the ground is kept.
+fn read_output_system(
+    mut q: Query<(&mut FirstPersonPawn, &KinematicCharacterControllerOutput), Without<Dead>>,
 ) {
-    for (mut pawn, mut transform, output) in q.iter_mut() {
+    for (mut pawn, output) in q.iter_mut() {
         pawn.grounded = output.grounded;
-        transform.translation += output.effective_translation;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pawns::{Controller, Possesses};
+    use bevy::render::mesh::MeshPlugin;
+    use bevy::scene::ScenePlugin;
+    use bevy::time::TimeUpdateStrategy;
+    use std::time::Duration;
+
+    const DT: f32 = 1.0 / 60.0;
+
+    #[test]
+    fn pawns_move_as_far_as_their_velocity_takes_them() {
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            AssetPlugin::default(),
+            ScenePlugin,
+            MeshPlugin,
+            TransformPlugin,
+            RapierPhysicsPlugin::<NoUserData>::default().in_fixed_schedule(),
+            FirstPersonPawnPlugin,
+        ))
+        .insert_resource(Time::<Fixed>::from_seconds(DT as f64))
+        .insert_resource(TimestepMode::Fixed {
+            dt: DT,
+            substeps: 1,
+        })
+        // one fixed tick per update
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(
+            DT,
+        )));
+        app.world_mut().spawn((
+            Collider::cuboid(50.0, 0.5, 50.0),
+            Transform::from_xyz(0.0, -0.5, 0.0),
+        ));
+        let pawn = app.world_mut().spawn(FirstPersonPawn::default()).id();
+        app.world_mut().spawn((
+            Controller(crate::Command {
+                forward: true,
+                ..Default::default()
+            }),
+            Possesses(pawn),
+        ));
+
+        for _ in 0..60 {
+            app.update();
+        }
+        let before = app.world().get::<Transform>(pawn).unwrap().translation;
+        app.update();
+        let after = app.world().get::<Transform>(pawn).unwrap().translation;
+        let velocity = app.world().get::<Velocity>(pawn).unwrap().linvel;
+
+        assert!(app.world().get::<FirstPersonPawn>(pawn).unwrap().grounded);
+        assert!(velocity.z < 0.0);
+        let moved = (after - before).with_y(0.0);
+        assert!(
+            moved.distance(velocity.with_y(0.0) * DT) < 1e-4,
+            "moved {moved} at {velocity}"
+        );
     }
 }
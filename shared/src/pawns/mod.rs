@@ -1,2 +1,119 @@
+use crate::Command;
+use bevy::ecs::component::Mutable;
+use bevy::prelude::*;
+
 pub mod fly;
 pub mod fps;
+pub mod spectator;
+
+/// Pawns are entities that can be possessed and steered by a [Controller].
+pub trait Pawn: Component {
+    /// Per tick input of the pawn, written before the pawn is simulated.
+    type Command: Component<Mutability = Mutable> + Default;
+
+    /// Translates a controller command into the input this pawn understands.
+    fn command(command: &Command) -> Self::Command;
+
+    /// Input of the pawn once nobody possesses it, based on its last one.
+    /// Lets go of everything by default, pawns which look around should keep their view angles.
+    fn release(_command: &Self::Command) -> Self::Command {
+        Self::Command::default()
+    }
+}
+
+pub trait PawnAppExt {
+    fn register_pawn<P: Pawn>(&mut self) -> &mut Self;
+}
+
+impl PawnAppExt for App {
+    fn register_pawn<P: Pawn>(&mut self) -> &mut Self {
+        self.add_systems(FixedPreUpdate, control::<P>.in_set(PawnControlSystems))
+    }
+}
+
+/// Systems which copy controller commands into the pawns they possess.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PawnControlSystems;
+
+/// Anything that can possess a pawn, e.g. a player. Holds the current command.
+#[derive(Component, Debug, Default, Deref, DerefMut)]
+pub struct Controller(pub Command);
+
+/// Placed on a [Controller], points at the pawn it currently possesses.
+/// Prefer [PossessExt::possess] to insert it, since a pawn can only have one controller.
+#[derive(Component, Debug)]
+#[relationship(relationship_target = Possessed)]
+pub struct Possesses(pub Entity);
+
+/// Placed on a [Pawn], points back at its controller.
+#[derive(Component, Debug)]
+#[relationship_target(relationship = Possesses)]
+pub struct Possessed(Entity);
+
+impl Possessed {
+    pub fn controller(&self) -> Entity {
+        self.0
+    }
+}
+
+pub trait PossessExt {
+    /// Possesses the given pawn, taking it over from its previous controller.
+    fn possess(&mut self, pawn: Entity) -> &mut Self;
+}
+
+impl PossessExt for EntityCommands<'_> {
+    fn possess(&mut self, pawn: Entity) -> &mut Self {
+        let controller = self.id();
+        self.commands().queue(move |world: &mut World| {
+            let previous = world.get::<Possessed>(pawn).map(Possessed::controller);
+            if let Some(previous) = previous.filter(|&previous| previous != controller) {
+                world.entity_mut(previous).remove::<Possesses>();
+            }
+            world.entity_mut(controller).insert(Possesses(pawn));
+        });
+        self
+    }
+}
+
+fn control<P: Pawn>(
+    q_controller: Query<&Controller>,
+    mut q_pawn: Query<(&mut P::Command, Option<&Possessed>), With<P>>,
+) {
+    for (mut command, possessed) in q_pawn.iter_mut() {
+        *command =
+            match possessed.and_then(|possessed| q_controller.get(possessed.controller()).ok()) {
+                Some(controller) => P::command(controller),
+                None => P::release(&command),
+            };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pawns::fps::{FirstPersonPawn, FirstPersonPawnCommand};
+    use bevy::ecs::system::RunSystemOnce;
+
+    #[test]
+    fn released_pawns_keep_their_view_angles() {
+        let mut world = World::new();
+        let pawn = world.spawn(FirstPersonPawn::default()).id();
+        let command = Command {
+            angle: Vec2::new(1.0, 0.5),
+            forward: true,
+            ..Default::default()
+        };
+        let controller = world.spawn((Controller(command), Possesses(pawn))).id();
+        world.run_system_once(control::<FirstPersonPawn>).unwrap();
+        assert_eq!(
+            world.get::<FirstPersonPawnCommand>(pawn).unwrap().angle,
+            Vec2::new(1.0, 0.5)
+        );
+
+        world.entity_mut(controller).remove::<Possesses>();
+        world.run_system_once(control::<FirstPersonPawn>).unwrap();
+        let command = world.get::<FirstPersonPawnCommand>(pawn).unwrap();
+        assert_eq!(command.angle, Vec2::new(1.0, 0.5));
+        assert_eq!(command.direction, Vec3::ZERO);
+    }
+}
@@ -0,0 +1,150 @@
+use crate::pawns::fly::{self, FlyPawn};
+use crate::pawns::fps::{EYE_OFFSET, FirstPersonPawn};
+use crate::pawns::{Pawn, PawnAppExt};
+use bevy::prelude::*;
+
+pub struct SpectatorPawnPlugin;
+
+impl Plugin for SpectatorPawnPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_pawn::<Spectator>().add_systems(
+            FixedUpdate,
+            (switch_system, follow_system)
+                .chain()
+                .after(fly::simulate_system),
+        );
+    }
+}
+
+/// Distance kept to the followed pawn in [SpectatorMode::ThirdPerson].
+const THIRD_PERSON_DISTANCE: f32 = 4.0;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum SpectatorMode {
+    /// Fly around freely, see [FlyPawn].
+    #[default]
+    Free,
+    FirstPerson(Entity),
+    ThirdPerson(Entity),
+}
+
+impl SpectatorMode {
+    pub fn target(&self) -> Option<Entity> {
+        match *self {
+            SpectatorMode::Free => None,
+            SpectatorMode::FirstPerson(target) | SpectatorMode::ThirdPerson(target) => Some(target),
+        }
+    }
+}
+
+#[derive(Component, Default)]
+pub struct SpectatorCommand {
+    angle: Vec2,
+    next_target: bool,
+    next_mode: bool,
+}
+
+/// A [FlyPawn] that can also follow other pawns.
+/// `fire` cycles through the pawns to follow, `jump` cycles between first person, third person and free flight.
+#[derive(Component, Default)]
+#[require(SpectatorCommand)]
+pub struct Spectator {
+    pub mode: SpectatorMode,
+    last_next_target: bool,
+    last_next_mode: bool,
+}
+
+impl Pawn for Spectator {
+    type Command = SpectatorCommand;
+
+    fn command(command: &crate::Command) -> Self::Command {
+        SpectatorCommand {
+            angle: command.view_angles(),
+            next_target: command.fire,
+            next_mode: command.jump,
+        }
+    }
+
+    fn release(command: &Self::Command) -> Self::Command {
+        SpectatorCommand {
+            angle: command.angle,
+            ..Default::default()
+        }
+    }
+}
+
+fn switch_system(
+    mut q: Query<(&mut Spectator, &SpectatorCommand)>,
+    q_targets: Query<Entity, With<FirstPersonPawn>>,
+) {
+    for (mut spectator, command) in q.iter_mut() {
+        let next_target = command.next_target && !spectator.last_next_target;
+        let next_mode = command.next_mode && !spectator.last_next_mode;
+        spectator.last_next_target = command.next_target;
+        spectator.last_next_mode = command.next_mode;
+
+        let mut targets = q_targets.iter().collect::<Vec<_>>();
+        targets.sort();
+
+        // fall back to free flight if the followed pawn is gone
+        let current = spectator.mode.target();
+        if current.is_some_and(|target| !targets.contains(&target)) {
+            spectator.mode = SpectatorMode::Free;
+        }
+
+        if next_target {
+            let target = match current.and_then(|c| targets.iter().position(|&t| t == c)) {
+                Some(index) => targets[(index + 1) % targets.len()],
+                None => match targets.first() {
+                    Some(&target) => target,
+                    None => continue,
+                },
+            };
+            spectator.mode = match spectator.mode {
+                SpectatorMode::ThirdPerson(_) => SpectatorMode::ThirdPerson(target),
+                _ => SpectatorMode::FirstPerson(target),
+            };
+        }
+
+        if next_mode {
+            spectator.mode = match spectator.mode {
+                SpectatorMode::FirstPerson(target) => SpectatorMode::ThirdPerson(target),
+                SpectatorMode::ThirdPerson(_) => SpectatorMode::Free,
+                SpectatorMode::Free => match targets.first() {
+                    Some(&target) => SpectatorMode::FirstPerson(target),
+                    None => SpectatorMode::Free,
+                },
+            };
+        }
+    }
+}
+
+fn follow_system(
+    mut q: Query<(&Spectator, &SpectatorCommand, &mut Transform), With<FlyPawn>>,
+    q_targets: Query<(&FirstPersonPawn, &Transform), Without<Spectator>>,
+) {
+    for (spectator, command, mut transform) in q.iter_mut() {
+        let Some((target, target_transform)) = spectator
+            .mode
+            .target()
+            .and_then(|target| q_targets.get(target).ok())
+        else {
+            continue;
+        };
+
+        let eye = target_transform.translation + EYE_OFFSET;
+        match spectator.mode {
+            SpectatorMode::FirstPerson(_) => {
+                transform.translation = eye;
+                transform.rotation = target.view_rotation();
+            }
+            SpectatorMode::ThirdPerson(_) => {
+                let rotation =
+                    Quat::from_euler(EulerRot::YXZ, command.angle.x, command.angle.y, 0.0);
+                transform.translation = eye + rotation * Vec3::Z * THIRD_PERSON_DISTANCE;
+                transform.rotation = rotation;
+            }
+            SpectatorMode::Free => {}
+        }
+    }
+}
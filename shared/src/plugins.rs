@@ -1,8 +1,16 @@
+use crate::conditioner::ConditionerPlugin;
+use crate::console::CommandPlugin;
 use crate::consts::TICK_RATE;
+use crate::health::HealthPlugin;
 use crate::interpolate::InterpolatePlugin;
+use crate::maps::MapPlugin;
+use crate::movers::MoverPlugin;
 use crate::pawns::fly::FlyPawnPlugin;
 use crate::pawns::fps::FirstPersonPawnPlugin;
+use crate::pawns::spectator::SpectatorPawnPlugin;
+use crate::projectiles::ProjectilePlugin;
 use crate::session::SessionPlugin;
+use crate::weapons::WeaponPlugin;
 use bevy::app::PluginGroupBuilder;
 use bevy::prelude::*;
 use bevy_rapier3d::prelude::*;
@@ -13,11 +21,19 @@ impl PluginGroup for SharedPlugins {
     fn build(self) -> PluginGroupBuilder {
         PluginGroupBuilder::start::<Self>()
             .add(SharedPlugin)
+            .add(CommandPlugin)
             .add(RapierPhysicsPlugin::<NoUserData>::default().in_fixed_schedule())
             .add(FirstPersonPawnPlugin)
             .add(FlyPawnPlugin)
+            .add(SpectatorPawnPlugin)
+            .add(HealthPlugin)
+            .add(WeaponPlugin)
+            .add(ProjectilePlugin)
             .add(InterpolatePlugin)
+            .add(MapPlugin)
+            .add(MoverPlugin)
             .add(SessionPlugin)
+            .add(ConditionerPlugin)
     }
 }
 
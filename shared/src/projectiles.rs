@@ -0,0 +1,177 @@
+use crate::interpolate::InterpolateTranslation;
+use crate::pawns::fps::{FirstPersonPawn, PAWN_MASS};
+use crate::weapons::{Delivery, Loadout, ShotEvent, simulate_weapons};
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Grenades, rockets and their explosions. Both sides launch projectiles as shots are fired,
+/// so clients see their own right away, but only the server decides when they go off.
+pub struct ProjectilePlugin;
+
+impl Plugin for ProjectilePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ExplosionEvent>().add_systems(
+            FixedUpdate,
+            (
+                launch_projectiles.after(simulate_weapons),
+                age_projectiles,
+                apply_explosions,
+            ),
+        );
+    }
+}
+
+/// Projectiles start this far ahead of the shooter's eyes, outside of its collider.
+const LAUNCH_DISTANCE: f32 = 0.8;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProjectileStats {
+    pub speed: f32,
+    pub radius: f32,
+    /// Multiplier of gravity, `0` for projectiles flying straight.
+    pub gravity_scale: f32,
+    /// How much speed is kept when bouncing, in `[0, 1]`.
+    pub restitution: f32,
+    /// Seconds after which the projectile goes off on its own.
+    pub fuse: f32,
+    pub impact: Impact,
+    pub explosion: Explosion,
+}
+
+/// What happens when a projectile hits something.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Impact {
+    /// Goes off on any contact.
+    Detonate,
+    /// Bounces off the world, but goes off when hitting an actor.
+    DetonateOnActors,
+    /// Bounces off everything, waiting for its fuse.
+    Bounce,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Explosion {
+    pub radius: f32,
+    /// Damage at the center, decreasing towards the edge.
+    pub damage: f32,
+    /// Impulse at the center, decreasing towards the edge.
+    pub impulse: f32,
+}
+
+impl Explosion {
+    /// Fraction of damage and impulse dealt at the given distance from the center.
+    pub fn factor(&self, distance: f32) -> f32 {
+        (1.0 - distance / self.radius).max(0.0)
+    }
+}
+
+#[derive(Component, Debug, Clone)]
+#[require(
+    RigidBody::Dynamic,
+    Ccd::enabled(),
+    ActiveEvents::COLLISION_EVENTS,
+    Velocity,
+    InterpolateTranslation
+)]
+pub struct Projectile {
+    /// Pawn which launched the projectile, if known on this side.
+    pub owner: Option<Entity>,
+    pub stats: ProjectileStats,
+    /// Seconds since launch.
+    pub age: f32,
+}
+
+impl Projectile {
+    pub fn launch(
+        owner: Option<Entity>,
+        stats: ProjectileStats,
+        translation: Vec3,
+        velocity: Vec3,
+    ) -> impl Bundle {
+        (
+            Collider::ball(stats.radius),
+            GravityScale(stats.gravity_scale),
+            Restitution::coefficient(stats.restitution),
+            Velocity::linear(velocity),
+            Transform::from_translation(translation),
+            Self {
+                owner,
+                stats,
+                age: 0.0,
+            },
+        )
+    }
+}
+
+/// Something went off, pushing away what is around it.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct ExplosionEvent {
+    pub position: Vec3,
+    pub explosion: Explosion,
+    /// Pawn the explosion is credited to, if any.
+    pub source: Option<Entity>,
+}
+
+pub fn launch_projectiles(
+    mut commands: Commands,
+    mut evr_shot: EventReader<ShotEvent>,
+    q_loadout: Query<&Loadout>,
+) {
+    for shot in evr_shot.read() {
+        let Some(Delivery::Projectile(stats)) = q_loadout
+            .get(shot.shooter)
+            .ok()
+            .and_then(|loadout| loadout.weapons.get(shot.weapon))
+            .map(|weapon| &weapon.stats.delivery)
+        else {
+            continue;
+        };
+        commands.spawn(Projectile::launch(
+            Some(shot.shooter),
+            stats.clone(),
+            shot.origin + shot.direction * LAUNCH_DISTANCE,
+            shot.direction * stats.speed,
+        ));
+    }
+}
+
+fn age_projectiles(mut q_projectiles: Query<&mut Projectile>, time: Res<Time>) {
+    for mut projectile in q_projectiles.iter_mut() {
+        projectile.age += time.delta_secs();
+    }
+}
+
+fn apply_explosions(
+    mut commands: Commands,
+    mut evr_explosion: EventReader<ExplosionEvent>,
+    q_bodies: Query<(Entity, &RigidBody, &GlobalTransform)>,
+    mut q_pawns: Query<(&Transform, &mut Velocity), With<FirstPersonPawn>>,
+) {
+    for ev in evr_explosion.read() {
+        let push = |position: Vec3| {
+            let offset = position - ev.position;
+            let factor = ev.explosion.factor(offset.length());
+            (factor > 0.0).then(|| offset.normalize_or(Vec3::Y) * ev.explosion.impulse * factor)
+        };
+
+        for (entity, body, transform) in q_bodies.iter() {
+            if *body != RigidBody::Dynamic {
+                continue;
+            }
+            if let Some(impulse) = push(transform.translation()) {
+                commands.entity(entity).insert(ExternalImpulse {
+                    impulse,
+                    torque_impulse: Vec3::ZERO,
+                });
+            }
+        }
+
+        // pawns are moved by their controller, their velocity takes the impulse
+        for (transform, mut velocity) in q_pawns.iter_mut() {
+            if let Some(impulse) = push(transform.translation) {
+                velocity.linvel += impulse / PAWN_MASS;
+            }
+        }
+    }
+}
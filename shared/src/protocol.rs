@@ -0,0 +1,135 @@
+use crate::Command;
+use crate::channels::{Channel, NetMessage};
+use crate::game::{FlagPlacements, Scoreboard};
+use crate::movers::MoverState;
+use crate::projectiles::{Explosion, ProjectileStats};
+use crate::session::Team;
+use crate::weapons::LoadoutState;
+use bevy::math::{Quat, Vec3};
+use serde::{Deserialize, Serialize};
+
+/// Messages sent from a client to the server.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ClientMessage {
+    /// First message of every connection, nothing else is accepted before it.
+    /// Must stay the first variant and keep its layout, so any version can still be rejected.
+    Hello(Hello),
+    /// Inputs for the next simulated tick.
+    Command(Command),
+    /// Sent regularly, so the server knows the client is still there.
+    Heartbeat,
+    /// Instead of [ClientMessage::Hello], takes over a session the client dropped out of.
+    Resume { token: u64 },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Hello {
+    pub version: u32,
+    pub build: String,
+    pub name: String,
+    /// Team the player would like to join, `None` to be assigned one.
+    pub team: Option<Team>,
+}
+
+impl NetMessage for ClientMessage {
+    fn channel(&self) -> Channel {
+        match self {
+            Self::Hello(_) | Self::Resume { .. } => Channel::Events,
+            Self::Command(_) => Channel::Input,
+            Self::Heartbeat => Channel::Heartbeat,
+        }
+    }
+}
+
+/// Messages sent from the server to its clients.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ServerMessage {
+    /// The handshake succeeded, the client has joined the game.
+    /// The token lets it resume its session, should it drop out.
+    Welcome {
+        token: u64,
+        /// Actor id of the client's pawn.
+        actor: u64,
+    },
+    /// The handshake failed, the server will not accept the client.
+    Rejected { reason: String },
+    /// Average time the server spent per update over the last second, in seconds.
+    Stats { tick_time: f32 },
+    /// Sent regularly, so clients know the server is still there.
+    Heartbeat,
+    /// The session a client tried to resume is gone, it has to join with a [ClientMessage::Hello].
+    Expired,
+    /// Health of an actor changed, e.g. it took damage or died.
+    Health { actor: u64, health: f32 },
+    /// A dead actor came back to life at the given position.
+    Respawn { actor: u64, translation: Vec3 },
+    /// Weapons of an actor changed, or are sent again so predictions which went wrong get fixed.
+    Loadout { actor: u64, state: LoadoutState },
+    /// A projectile was launched, by the pawn of the `owner` actor if it is still around.
+    Launched {
+        actor: u64,
+        owner: Option<u64>,
+        stats: ProjectileStats,
+        translation: Vec3,
+        velocity: Vec3,
+    },
+    /// A projectile went off.
+    Detonated {
+        actor: u64,
+        position: Vec3,
+        explosion: Explosion,
+    },
+    /// Map to play on, with the hash of its file to check it is the same one.
+    Map { name: String, hash: u64 },
+    /// Scores and the state of the round, sent regularly.
+    Scoreboard(Scoreboard),
+    /// Flags moved, or are sent again for clients which joined since.
+    Flags(FlagPlacements),
+    /// State of the map's dynamic bodies which moved, and now and then of all of them.
+    /// Counts up with every snapshot, so outdated ones can be told apart.
+    Bodies {
+        sequence: u32,
+        bodies: Vec<BodyState>,
+    },
+    /// State of movers which were set in motion, and now and then of all of them.
+    Movers(Vec<MoverUpdate>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BodyState {
+    /// Index of the body's node in the map, see [crate::maps::MapNode].
+    pub node: u32,
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub linvel: Vec3,
+    pub angvel: Vec3,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MoverUpdate {
+    /// Index of the mover's node in the map, see [crate::maps::MapNode].
+    pub node: u32,
+    pub state: MoverState,
+}
+
+impl NetMessage for ServerMessage {
+    fn channel(&self) -> Channel {
+        match self {
+            Self::Welcome { .. }
+            | Self::Rejected { .. }
+            | Self::Stats { .. }
+            | Self::Expired
+            | Self::Health { .. }
+            | Self::Respawn { .. }
+            | Self::Loadout { .. }
+            | Self::Launched { .. }
+            | Self::Detonated { .. }
+            | Self::Map { .. }
+            | Self::Scoreboard(_)
+            | Self::Flags(_)
+            | Self::Movers(_) => Channel::Events,
+            Self::Bodies { .. } => Channel::Snapshots,
+            Self::Heartbeat => Channel::Heartbeat,
+        }
+    }
+}
@@ -0,0 +1,54 @@
+//! Lossy encodings used to keep network messages small.
+
+use std::f32::consts::{PI, TAU};
+
+/// Maps an angle onto the full range of a [u16], wrapping around once per turn.
+pub fn angle_to_u16(angle: f32) -> u16 {
+    ((angle.rem_euclid(TAU) / TAU) * 65536.0).round() as u32 as u16
+}
+
+/// Inverse of [angle_to_u16], the result is in the range `(-PI, PI]`.
+pub fn angle_from_u16(value: u16) -> f32 {
+    let angle = value as f32 / 65536.0 * TAU;
+    if angle > PI { angle - TAU } else { angle }
+}
+
+/// Maps a value in the range `[0, 1]` onto a [u8].
+pub fn fraction_to_u8(fraction: f32) -> u8 {
+    (fraction.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+/// Inverse of [fraction_to_u8].
+pub fn fraction_from_u8(value: u8) -> f32 {
+    value as f32 / 255.0
+}
+
+/// Serde adapter for view angles, see [crate::Command::angle].
+pub mod angles {
+    use super::{angle_from_u16, angle_to_u16};
+    use bevy::math::Vec2;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(angle: &Vec2, serializer: S) -> Result<S::Ok, S::Error> {
+        (angle_to_u16(angle.x), angle_to_u16(angle.y)).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec2, D::Error> {
+        let (x, y) = <(u16, u16)>::deserialize(deserializer)?;
+        Ok(Vec2::new(angle_from_u16(x), angle_from_u16(y)))
+    }
+}
+
+/// Serde adapter for fractions, see [crate::InputEvent::time].
+pub mod fraction {
+    use super::{fraction_from_u8, fraction_to_u8};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(fraction: &f32, serializer: S) -> Result<S::Ok, S::Error> {
+        fraction_to_u8(*fraction).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f32, D::Error> {
+        u8::deserialize(deserializer).map(fraction_from_u8)
+    }
+}
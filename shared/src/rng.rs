@@ -0,0 +1,59 @@
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+
+/// Mixed into seeds, so small ones still give a well mixed state.
+const SEED_MIX: u64 = 0x9e37_79b9_7f4a_7c15;
+
+/// Small xorshift generator, for simulations which don't need good randomness.
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // xorshift must not be seeded with zero, it would only ever return zero
+        match seed ^ SEED_MIX {
+            0 => Self(SEED_MIX),
+            state => Self(state),
+        }
+    }
+
+    /// Seeded differently on every call, for generators which have to be independent of each other.
+    pub fn from_entropy() -> Self {
+        Self::new(RandomState::new().hash_one(0u8))
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// Uniformly distributed in `[0, 1)`.
+    pub fn fraction(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    pub fn chance(&mut self, probability: f32) -> bool {
+        self.fraction() < probability
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn never_seeded_with_zero() {
+        let mut rng = Rng::new(SEED_MIX);
+        assert_ne!(rng.next_u64(), 0);
+    }
+
+    #[test]
+    fn entropy_seeds_differ() {
+        assert_ne!(
+            Rng::from_entropy().next_u64(),
+            Rng::from_entropy().next_u64()
+        );
+    }
+}
@@ -1,4 +1,7 @@
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
 
 pub struct SessionPlugin;
 
@@ -16,6 +19,11 @@ pub struct Actor {
 }
 
 impl Actor {
+    /// An actor whose id was assigned elsewhere, e.g. by the server.
+    pub fn new(id: u64) -> Self {
+        Self { id }
+    }
+
     pub fn id(&self) -> u64 {
         self.id
     }
@@ -33,3 +41,35 @@ impl Session {
         Actor { id }
     }
 }
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Team {
+    Red,
+    Blue,
+}
+
+impl Team {
+    pub const ALL: [Team; 2] = [Team::Red, Team::Blue];
+}
+
+impl FromStr for Team {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "red" => Ok(Self::Red),
+            "blue" => Ok(Self::Blue),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Display for Team {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Red => "red",
+            Self::Blue => "blue",
+        })
+    }
+}
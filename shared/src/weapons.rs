@@ -0,0 +1,543 @@
+use crate::health::Dead;
+use crate::pawns::fps::{EYE_OFFSET, FirstPersonPawn, FirstPersonPawnCommand, simulate_system};
+use crate::projectiles::ProjectileStats;
+use crate::rng::Rng;
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, LoadContext};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::TAU;
+use std::io::ErrorKind;
+
+/// Firing, ammunition and reloading. Simulated on both sides, so clients can predict their own
+/// shots, while only the server decides what they hit.
+pub struct WeaponPlugin;
+
+impl Plugin for WeaponPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<Arsenal>()
+            .register_asset_loader(ArsenalLoader)
+            .add_event::<ShotEvent>()
+            .add_systems(Startup, load_arsenal)
+            .add_systems(
+                FixedUpdate,
+                (equip_loadouts, simulate_weapons)
+                    .chain()
+                    .after(simulate_system),
+            );
+    }
+}
+
+/// Slots commands can select a weapon from, one per key. Weapons of the arsenal past them are
+/// out of reach.
+pub const WEAPON_SLOTS: usize = 4;
+
+/// File in `assets` the weapons of every pawn are read from.
+const ARSENAL_PATH: &str = "default.arsenal.ron";
+
+/// Timers count as run out this close to zero, so rounding errors don't cost a whole tick.
+const TIMER_EPSILON: f32 = 1e-4;
+
+/// Weapons every pawn carries, in the order of their slots, read from a RON file.
+#[derive(Asset, TypePath, Debug, Clone, Deserialize)]
+#[serde(transparent)]
+pub struct Arsenal(pub Vec<WeaponStats>);
+
+#[derive(Default)]
+struct ArsenalLoader;
+
+impl AssetLoader for ArsenalLoader {
+    type Asset = Arsenal;
+    type Settings = ();
+    type Error = std::io::Error;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Arsenal, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        ron::de::from_bytes(&bytes).map_err(|err| std::io::Error::new(ErrorKind::InvalidData, err))
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["arsenal.ron"]
+    }
+}
+
+/// The arsenal pawns are equipped from.
+#[derive(Resource, Debug)]
+struct DefaultArsenal(Handle<Arsenal>);
+
+fn load_arsenal(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(DefaultArsenal(asset_server.load(ARSENAL_PATH)));
+}
+
+/// Hands out weapons to pawns which have none, e.g. because they were spawned before the
+/// arsenal was loaded.
+fn equip_loadouts(
+    arsenal: Res<DefaultArsenal>,
+    arsenals: Res<Assets<Arsenal>>,
+    mut q_loadouts: Query<&mut Loadout>,
+) {
+    let Some(arsenal) = arsenals.get(&arsenal.0) else {
+        return;
+    };
+    for mut loadout in q_loadouts.iter_mut() {
+        if loadout.weapons.is_empty() {
+            *loadout = Loadout::new(arsenal.0.clone());
+        }
+    }
+}
+
+/// Everything that sets one weapon apart from another, plain data so it can be tuned freely.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WeaponStats {
+    pub name: String,
+    /// Seconds between two shots.
+    pub fire_interval: f32,
+    /// Keeps firing while the trigger is held, otherwise every shot needs its own press.
+    pub automatic: bool,
+    /// Shots per magazine.
+    pub magazine: u32,
+    /// Seconds a reload takes.
+    pub reload_time: f32,
+    /// Half angle of the cone shots scatter in, in radians.
+    pub spread: f32,
+    /// Offsets of consecutive shots from where the weapon is aimed, yaw in `x` and pitch in `y`,
+    /// in radians. The last one repeats for longer bursts.
+    pub recoil: Vec<Vec2>,
+    /// Damage of a hit, projectiles deal theirs by exploding instead.
+    pub damage: f32,
+    #[serde(default)]
+    pub falloff: Falloff,
+    pub delivery: Delivery,
+}
+
+/// How damage decreases with distance.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Falloff {
+    /// Distance up to which the full damage is dealt.
+    pub start: f32,
+    /// Distance from which only the minimum damage is dealt.
+    pub end: f32,
+    /// Fraction of the damage dealt beyond the end.
+    pub min: f32,
+}
+
+impl Default for Falloff {
+    fn default() -> Self {
+        Self::NONE
+    }
+}
+
+impl Falloff {
+    /// No falloff at all.
+    pub const NONE: Self = Self {
+        start: f32::INFINITY,
+        end: f32::INFINITY,
+        min: 1.0,
+    };
+
+    /// Fraction of the damage dealt at the given distance.
+    pub fn factor(&self, distance: f32) -> f32 {
+        if distance <= self.start {
+            return 1.0;
+        }
+        let t = ((distance - self.start) / (self.end - self.start)).min(1.0);
+        1.0 + (self.min - 1.0) * t
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Delivery {
+    /// Hits whatever is in the line of fire instantly.
+    Hitscan { range: f32 },
+    /// Launches a projectile, which travels on its own.
+    Projectile(ProjectileStats),
+}
+
+/// A weapon along with its state.
+#[derive(Debug, Clone)]
+pub struct Weapon {
+    pub stats: WeaponStats,
+    pub ammo: u32,
+    /// Seconds until the next shot may be fired.
+    pub cooldown: f32,
+    /// Seconds until the running reload is done.
+    pub reloading: Option<f32>,
+    /// Shots fired since the trigger was last let go, which selects the recoil offset.
+    pub burst: usize,
+}
+
+impl Weapon {
+    pub fn new(stats: WeaponStats) -> Self {
+        Self {
+            ammo: stats.magazine,
+            stats,
+            cooldown: 0.0,
+            reloading: None,
+            burst: 0,
+        }
+    }
+
+    fn reload(&mut self) {
+        if self.reloading.is_none() && self.ammo < self.stats.magazine {
+            self.reloading = Some(self.stats.reload_time);
+        }
+    }
+}
+
+/// The weapons a pawn carries, of which one is held at a time.
+/// Empty by default, until the pawn is equipped from the arsenal.
+#[derive(Component, Debug, Clone)]
+pub struct Loadout {
+    pub weapons: Vec<Weapon>,
+    /// Index of the weapon in hand.
+    pub current: usize,
+    rng: Rng,
+}
+
+impl Default for Loadout {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+impl Loadout {
+    pub fn new(weapons: Vec<WeaponStats>) -> Self {
+        Self {
+            weapons: weapons.into_iter().map(Weapon::new).collect(),
+            current: 0,
+            rng: Rng::new(0),
+        }
+    }
+
+    /// Fills up all magazines again, e.g. on respawning.
+    pub fn refill(&mut self) {
+        for weapon in &mut self.weapons {
+            *weapon = Weapon::new(weapon.stats.clone());
+        }
+    }
+
+    pub fn current(&self) -> Option<&Weapon> {
+        self.weapons.get(self.current)
+    }
+
+    /// The parts of the loadout the server replicates.
+    pub fn state(&self) -> LoadoutState {
+        LoadoutState {
+            current: self.current as u8,
+            ammo: self.weapons.iter().map(|weapon| weapon.ammo).collect(),
+            reloading: self
+                .current()
+                .is_some_and(|weapon| weapon.reloading.is_some()),
+        }
+    }
+
+    /// Takes over a replicated state. A reload it has, but this loadout lacks, starts over.
+    pub fn apply(&mut self, state: &LoadoutState) {
+        let current = usize::from(state.current);
+        if current < self.weapons.len() {
+            self.current = current;
+        }
+        for (index, (weapon, ammo)) in self.weapons.iter_mut().zip(&state.ammo).enumerate() {
+            weapon.ammo = *ammo;
+            if index != self.current || !state.reloading {
+                weapon.reloading = None;
+            } else if weapon.reloading.is_none() {
+                weapon.reloading = Some(weapon.stats.reload_time);
+            }
+        }
+    }
+
+    /// Advances the weapons by a tick. Returns the offset of a shot from the aim,
+    /// yaw in `x` and pitch in `y`, if one was fired.
+    pub fn simulate(
+        &mut self,
+        command: &FirstPersonPawnCommand,
+        delta_seconds: f32,
+    ) -> Option<Vec2> {
+        if let Some(slot) = command.weapon.map(usize::from)
+            && slot < self.weapons.len()
+            && slot != self.current
+        {
+            // putting a weapon away cancels its reload
+            let previous = &mut self.weapons[self.current];
+            previous.reloading = None;
+            previous.burst = 0;
+            self.current = slot;
+        }
+
+        let weapon = self.weapons.get_mut(self.current)?;
+        // seconds into the tick at which the weapon is ready again
+        let ready = weapon.cooldown;
+        weapon.cooldown = (weapon.cooldown - delta_seconds).max(0.0);
+
+        if let Some(remaining) = &mut weapon.reloading {
+            *remaining -= delta_seconds;
+            if *remaining > TIMER_EPSILON {
+                return None;
+            }
+            weapon.reloading = None;
+            weapon.ammo = weapon.stats.magazine;
+        }
+
+        if command.reload {
+            weapon.reload();
+            return None;
+        }
+
+        if !command.fire && command.fire_pressed.is_none() {
+            weapon.burst = 0;
+            return None;
+        }
+        let pressed = command
+            .fire_pressed
+            .map(|fraction| fraction * delta_seconds);
+        let held = weapon.stats.automatic && command.fire;
+        // a pull of the trigger fires when it happened, holding it fires as soon as the weapon is ready
+        let shot_time = match pressed {
+            Some(pressed) if ready <= pressed + TIMER_EPSILON => pressed,
+            _ if held && ready <= delta_seconds + TIMER_EPSILON => ready,
+            _ => return None,
+        };
+        if weapon.ammo == 0 {
+            weapon.reload();
+            return None;
+        }
+
+        weapon.ammo -= 1;
+        // the interval counts from the shot, not from the end of the tick
+        weapon.cooldown = (weapon.stats.fire_interval - (delta_seconds - shot_time)).max(0.0);
+        let recoil = weapon
+            .stats
+            .recoil
+            .get(weapon.burst)
+            .or(weapon.stats.recoil.last())
+            .copied()
+            .unwrap_or_default();
+        weapon.burst += 1;
+
+        // evenly distributed over the cone's base
+        let angle = self.rng.fraction() * TAU;
+        let radius = self.rng.fraction().sqrt() * weapon.stats.spread;
+        Some(recoil + Vec2::from_angle(angle) * radius)
+    }
+}
+
+/// Slot, ammo and reloading of a [Loadout], everything that decides whether it can fire.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LoadoutState {
+    /// Index of the weapon in hand.
+    pub current: u8,
+    /// Ammo of every weapon, in the order of the loadout.
+    pub ammo: Vec<u32>,
+    /// Whether the weapon in hand is being reloaded.
+    pub reloading: bool,
+}
+
+/// A shot fired by a pawn, for the server to find out what it hits, or to launch a projectile.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct ShotEvent {
+    pub shooter: Entity,
+    /// Index of the weapon in the shooter's [Loadout].
+    pub weapon: usize,
+    pub origin: Vec3,
+    pub direction: Dir3,
+}
+
+pub fn simulate_weapons(
+    mut q_pawns: Query<
+        (
+            Entity,
+            &FirstPersonPawn,
+            &FirstPersonPawnCommand,
+            &Transform,
+            &mut Loadout,
+        ),
+        Without<Dead>,
+    >,
+    mut evw_shot: EventWriter<ShotEvent>,
+    time: Res<Time>,
+) {
+    for (entity, pawn, command, transform, mut loadout) in q_pawns.iter_mut() {
+        let Some(offset) = loadout.simulate(command, time.delta_secs()) else {
+            continue;
+        };
+        let rotation = Quat::from_euler(
+            EulerRot::YXZ,
+            pawn.yaw + offset.x,
+            pawn.pitch + offset.y,
+            0.0,
+        );
+        evw_shot.write(ShotEvent {
+            shooter: entity,
+            weapon: loadout.current,
+            origin: transform.translation + EYE_OFFSET,
+            direction: rotation * Dir3::NEG_Z,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DT: f32 = 1.0 / 60.0;
+
+    fn arsenal() -> Vec<WeaponStats> {
+        ron::from_str::<Arsenal>(include_str!("../../assets/default.arsenal.ron"))
+            .unwrap()
+            .0
+    }
+
+    fn stats(name: &str) -> WeaponStats {
+        arsenal()
+            .into_iter()
+            .find(|stats| stats.name == name)
+            .unwrap()
+    }
+
+    fn held() -> FirstPersonPawnCommand {
+        FirstPersonPawnCommand {
+            fire: true,
+            ..Default::default()
+        }
+    }
+
+    fn shots(loadout: &mut Loadout, command: &FirstPersonPawnCommand, ticks: usize) -> usize {
+        (0..ticks)
+            .filter(|_| loadout.simulate(command, DT).is_some())
+            .count()
+    }
+
+    #[test]
+    fn automatic_fires_at_interval_until_empty() {
+        let mut loadout = Loadout::new(vec![stats("rifle")]);
+        // almost a second of holding the trigger, at a shot every 0.1 s from the first tick on
+        assert_eq!(shots(&mut loadout, &held(), 59), 10);
+        assert_eq!(loadout.current().unwrap().ammo, 20);
+
+        assert_eq!(shots(&mut loadout, &held(), 180), 20);
+        let weapon = loadout.current().unwrap();
+        assert_eq!(weapon.ammo, 0);
+        assert!(weapon.reloading.is_some());
+    }
+
+    #[test]
+    fn reload_blocks_firing_and_refills() {
+        let mut loadout = Loadout::new(vec![stats("rifle")]);
+        shots(&mut loadout, &held(), 1);
+        let reload = FirstPersonPawnCommand {
+            reload: true,
+            ..Default::default()
+        };
+        loadout.simulate(&reload, DT);
+
+        // 2 s reload
+        assert_eq!(shots(&mut loadout, &held(), 110), 0);
+        shots(&mut loadout, &FirstPersonPawnCommand::default(), 10);
+        assert_eq!(loadout.current().unwrap().ammo, 30);
+        assert_eq!(shots(&mut loadout, &held(), 1), 1);
+    }
+
+    #[test]
+    fn semi_automatic_needs_a_press_per_shot() {
+        let mut loadout = Loadout::new(vec![stats("pistol")]);
+        let pressed = FirstPersonPawnCommand {
+            fire: true,
+            fire_pressed: Some(0.0),
+            ..Default::default()
+        };
+        assert_eq!(shots(&mut loadout, &pressed, 1), 1);
+        assert_eq!(shots(&mut loadout, &held(), 60), 0);
+    }
+
+    #[test]
+    fn presses_fire_at_their_time_within_the_tick() {
+        let pressed_at = |fraction| FirstPersonPawnCommand {
+            fire_pressed: Some(fraction),
+            ..Default::default()
+        };
+        let mut early = Loadout::new(vec![stats("pistol")]);
+        let mut late = Loadout::new(vec![stats("pistol")]);
+        for loadout in [&mut early, &mut late] {
+            assert_eq!(shots(loadout, &pressed_at(0.5), 1), 1);
+            shots(loadout, &FirstPersonPawnCommand::default(), 11);
+        }
+        // the 0.2 s interval runs from the middle of the first tick into the middle of the 13th
+        assert_eq!(shots(&mut early, &pressed_at(0.25), 1), 0);
+        assert_eq!(shots(&mut late, &pressed_at(0.75), 1), 1);
+    }
+
+    #[test]
+    fn switching_cancels_reload() {
+        let mut loadout = Loadout::new(arsenal());
+        shots(&mut loadout, &held(), 1);
+        loadout.simulate(
+            &FirstPersonPawnCommand {
+                reload: true,
+                ..Default::default()
+            },
+            DT,
+        );
+        loadout.simulate(
+            &FirstPersonPawnCommand {
+                weapon: Some(1),
+                ..Default::default()
+            },
+            DT,
+        );
+        assert_eq!(loadout.current, 1);
+        assert!(loadout.weapons[0].reloading.is_none());
+        assert_eq!(loadout.weapons[0].ammo, 29);
+    }
+
+    #[test]
+    fn applying_state_snaps_slot_ammo_and_reload() {
+        let mut server = Loadout::new(arsenal());
+        let mut client = Loadout::new(arsenal());
+        shots(&mut server, &held(), 1);
+        server.simulate(
+            &FirstPersonPawnCommand {
+                reload: true,
+                ..Default::default()
+            },
+            DT,
+        );
+        // the client predicted more shots than the server fired
+        shots(&mut client, &held(), 20);
+
+        client.apply(&server.state());
+        assert_eq!(client.state(), server.state());
+        assert_eq!(client.weapons[0].ammo, 29);
+        assert!(client.weapons[0].reloading.is_some());
+
+        server.simulate(
+            &FirstPersonPawnCommand {
+                weapon: Some(2),
+                ..Default::default()
+            },
+            DT,
+        );
+        client.apply(&server.state());
+        assert_eq!(client.current, 2);
+        assert!(client.weapons[0].reloading.is_none());
+    }
+
+    #[test]
+    fn falloff_interpolates_between_start_and_end() {
+        let falloff = Falloff {
+            start: 10.0,
+            end: 20.0,
+            min: 0.5,
+        };
+        assert_eq!(falloff.factor(5.0), 1.0);
+        assert_eq!(falloff.factor(15.0), 0.75);
+        assert_eq!(falloff.factor(100.0), 0.5);
+        assert_eq!(Falloff::NONE.factor(1000.0), 1.0);
+    }
+}